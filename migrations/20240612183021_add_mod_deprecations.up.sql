@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+create table mod_deprecations (
+    mod_id text primary key not null,
+    reason text not null,
+    successor_id text,
+    developer_id integer,
+    created_at timestamptz not null default now(),
+    foreign key (mod_id) references mods(id) on delete cascade,
+    foreign key (successor_id) references mods(id) on delete set null,
+    foreign key (developer_id) references developers(id) on delete set null
+);
+
+create index idx_mod_deprecations_successor_id on mod_deprecations(successor_id);
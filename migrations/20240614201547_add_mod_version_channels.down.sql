@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+drop index idx_mod_versions_channel;
+alter table mod_versions drop column channel;
+drop type mod_version_channel;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+create type mod_version_channel as enum('stable', 'beta', 'alpha');
+
+alter table mod_versions add column channel mod_version_channel not null default 'stable';
+
+update mod_versions set channel = cast(case
+        when split_part(version, '-', 2) ilike 'alpha%' then 'alpha'
+        when split_part(split_part(version, '+', 1), '-', 2) <> '' then 'beta'
+        else 'stable'
+    end as mod_version_channel);
+
+create index idx_mod_versions_channel on mod_versions(channel);
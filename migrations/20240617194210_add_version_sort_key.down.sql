@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP INDEX idx_mod_versions_mod_id_version_key;
+ALTER TABLE mod_versions DROP COLUMN version_key;
+DROP FUNCTION semver_sort_key(text);
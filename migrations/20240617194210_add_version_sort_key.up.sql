@@ -0,0 +1,39 @@
+-- Add up migration script here
+
+-- Builds a key that sorts (with the C collation) the same way as semver precedence:
+-- numeric core parts are zero padded, releases sort after prereleases and prerelease
+-- identifiers are compared numerically or lexically, as the spec says.
+-- Unparseable versions give NULL.
+CREATE OR REPLACE FUNCTION semver_sort_key(text)
+RETURNS text AS $$
+    SELECT CASE
+        WHEN portions IS NULL OR array_length(core, 1) <> 3 THEN NULL
+        ELSE
+            (SELECT string_agg(lpad(v, 10, '0'), '.' ORDER BY n) FROM unnest(core) WITH ORDINALITY x (v, n))
+            || CASE
+                WHEN portions[2] IS NULL THEN '1'
+                ELSE '0' || (
+                    SELECT string_agg(
+                        CASE
+                            WHEN v ~ '^\d+$' THEN '0' || lpad(length(v)::text, 3, '0') || v
+                            ELSE '1' || v
+                        END,
+                        chr(1) ORDER BY n
+                    )
+                    FROM unnest(string_to_array(portions[2], '.')) WITH ORDINALITY x (v, n)
+                )
+            END
+    END
+    FROM (
+        SELECT p, string_to_array(p[1], '.')
+        FROM (
+            SELECT regexp_match($1, '^([[:digit:].]+)(?:-([[:alnum:].-]+))?(?:\+[[:alnum:].-]+)?$')
+        ) x (p)
+    ) x (portions, core)
+$$ LANGUAGE SQL IMMUTABLE STRICT;
+
+ALTER TABLE mod_versions
+    ADD COLUMN version_key TEXT COLLATE "C"
+    GENERATED ALWAYS AS (semver_sort_key(version)) STORED;
+
+CREATE INDEX idx_mod_versions_mod_id_version_key ON mod_versions(mod_id, version_key DESC);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+create type gd_version as enum ('*', '2.113', '2.200', '2.204', '2.205', '2.206');
+
+alter table mod_gd_versions alter column gd type gd_version using gd::gd_version;
+
+drop table gd_version_platforms;
+drop table gd_versions;
@@ -0,0 +1,35 @@
+-- Add up migration script here
+
+create table gd_versions (
+    version text primary key not null,
+    current boolean not null default false,
+    deprecated boolean not null default false,
+    created_at timestamptz not null default now()
+);
+
+-- only one version can be the current one
+create unique index idx_gd_versions_current on gd_versions(current) where current;
+
+create table gd_version_platforms (
+    gd text not null,
+    platform gd_ver_platform not null,
+    primary key (gd, platform),
+    foreign key (gd) references gd_versions(version) on delete cascade on update cascade
+);
+
+insert into gd_versions (version, current) values
+    ('2.113', false),
+    ('2.200', false),
+    ('2.204', false),
+    ('2.205', false),
+    ('2.206', true);
+
+-- keep accepting every platform for the existing versions, admins can narrow it down later
+insert into gd_version_platforms (gd, platform)
+select gv.version, p.platform
+from gd_versions gv
+cross join unnest(enum_range(null::gd_ver_platform)) as p(platform);
+
+alter table mod_gd_versions alter column gd type text using gd::text;
+
+drop type gd_version;
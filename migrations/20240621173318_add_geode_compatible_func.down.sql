@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+DROP FUNCTION IF EXISTS geode_compatible(text, text);
@@ -0,0 +1,16 @@
+-- Add up migration script here
+
+-- Whether a mod version made for mod_geode can be loaded by the loader version.
+-- Alpha loaders only load mods made for that exact alpha. Otherwise the major
+-- version has to match, alpha mods are never picked and the mod can't require
+-- a newer version (minor, patch or beta) than the loader has.
+CREATE OR REPLACE FUNCTION geode_compatible(mod_geode text, loader text)
+RETURNS boolean AS $$
+    SELECT CASE
+        WHEN split_part(l, '-', 2) ILIKE 'alpha%' THEN m = l
+        ELSE split_part(m, '.', 1) = split_part(l, '.', 1)
+            AND split_part(m, '-', 2) NOT ILIKE 'alpha%'
+            AND semver_compare(m, l) <= 0
+    END
+    FROM (VALUES (ltrim($1, 'v'), ltrim($2, 'v'))) x (m, l)
+$$ LANGUAGE SQL IMMUTABLE STRICT;
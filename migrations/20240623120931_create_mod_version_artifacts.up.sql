@@ -0,0 +1,11 @@
+-- Add up migration script here
+
+create table mod_version_artifacts (
+    id serial primary key not null,
+    mod_version_id integer not null,
+    platform gd_ver_platform not null,
+    download_link text not null,
+    hash text not null,
+    foreign key (mod_version_id) references mod_versions(id) on delete cascade,
+    unique (mod_version_id, platform)
+);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+alter table mod_versions drop column tags_known;
+
+drop table mod_version_tags;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+create table mod_version_tags (
+    mod_version_id integer not null,
+    tag_id integer not null,
+    primary key (mod_version_id, tag_id),
+    foreign key (mod_version_id) references mod_versions(id) on delete cascade,
+    foreign key (tag_id) references mod_tags(id) on delete cascade
+);
+
+-- tags were only stored per mod until now, so what existing versions had is unknown
+alter table mod_versions add column tags_known boolean not null default true;
+update mod_versions set tags_known = false;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+drop table mod_version_status_history;
+drop type mod_version_status_event;
@@ -0,0 +1,33 @@
+-- Add up migration script here
+
+create type mod_version_status_event as enum('created', 'status_changed', 'replaced', 'withdrawn');
+
+-- mod_id and version are copied so the history survives a withdrawn version being deleted
+create table mod_version_status_history (
+    id serial primary key,
+    mod_id text not null,
+    version text not null,
+    mod_version_id integer,
+    event mod_version_status_event not null,
+    status mod_version_status,
+    info text,
+    developer_id integer,
+    created_at timestamptz not null default now(),
+    foreign key (mod_id) references mods(id) on update cascade on delete cascade,
+    foreign key (mod_version_id) references mod_versions(id) on delete set null,
+    foreign key (developer_id) references developers(id) on delete set null
+);
+
+create index idx_mod_version_status_history_mod_id_version on mod_version_status_history(mod_id, version);
+create index idx_mod_version_status_history_mod_version_id on mod_version_status_history(mod_version_id);
+
+-- only the current status is known for existing versions
+insert into mod_version_status_history (mod_id, version, mod_version_id, event, status, info, developer_id, created_at)
+select mv.mod_id, mv.version, mv.id,
+    cast(case
+        when mvs.admin_id is null then 'created'
+        else 'status_changed'
+    end as mod_version_status_event),
+    mvs.status, mvs.info, mvs.admin_id, mvs.updated_at
+from mod_versions mv
+inner join mod_version_statuses mvs on mvs.mod_version_id = mv.id;
@@ -0,0 +1,28 @@
+-- Add up migration script here
+
+create table collections (
+    id serial primary key,
+    developer_id integer not null,
+    name text not null,
+    description text,
+    created_at timestamptz not null default now(),
+    updated_at timestamptz not null default now(),
+    foreign key (developer_id) references developers(id) on delete cascade
+);
+
+create index idx_collections_developer_id on collections(developer_id);
+
+-- version and compare work like in the dependencies table, both are null when any version goes
+create table collection_mods (
+    collection_id integer not null,
+    mod_id text not null,
+    position integer not null,
+    version text,
+    compare version_compare,
+    note text,
+    primary key (collection_id, mod_id),
+    foreign key (collection_id) references collections(id) on delete cascade,
+    foreign key (mod_id) references mods(id) on update cascade on delete cascade
+);
+
+create index idx_collection_mods_mod_id on collection_mods(mod_id);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+drop index idx_mods_search_vector;
+drop function update_mod_search_vector;
+alter table mods drop column search_vector;
@@ -0,0 +1,28 @@
+-- Add up migration script here
+
+alter table mods add column search_vector tsvector not null default ''::tsvector;
+
+-- name and description come from the newest version, so search matches what the index shows
+create or replace function update_mod_search_vector(p_mod_id text) returns void as $$
+    update mods m set search_vector =
+        setweight(to_tsvector('english', coalesce(latest.name, '')), 'A') ||
+        setweight(to_tsvector('english', coalesce(latest.description, '')), 'B') ||
+        setweight(to_tsvector('english', coalesce(devs.names, '')), 'B') ||
+        setweight(to_tsvector('english', coalesce(m.about, '')), 'C')
+    from (
+        select mv.name, mv.description from mod_versions mv
+        where mv.mod_id = p_mod_id
+        order by mv.version_key desc nulls last, mv.id desc
+        limit 1
+    ) latest, (
+        select string_agg(d.display_name || ' ' || d.username, ' ') as names
+        from mods_developers md
+        inner join developers d on d.id = md.developer_id
+        where md.mod_id = p_mod_id
+    ) devs
+    where m.id = p_mod_id;
+$$ language sql;
+
+select update_mod_search_vector(id) from mods;
+
+create index idx_mods_search_vector on mods using gin(search_vector);
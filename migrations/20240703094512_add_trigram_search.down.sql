@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+drop index idx_mods_id_trgm;
+drop index idx_mod_versions_name_trgm;
+drop extension if exists pg_trgm;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+create extension if not exists pg_trgm;
+
+create index idx_mod_versions_name_trgm on mod_versions using gin (name gin_trgm_ops);
+create index idx_mods_id_trgm on mods using gin (id gin_trgm_ops);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+drop index idx_mod_downloads_time_downloaded;
+drop index idx_mods_trending_score;
+
+alter table mods drop column weekly_download_count;
+alter table mods drop column trending_score;
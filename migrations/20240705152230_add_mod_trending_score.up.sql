@@ -0,0 +1,7 @@
+-- Add up migration script here
+
+alter table mods add column trending_score double precision not null default 0;
+alter table mods add column weekly_download_count integer not null default 0;
+
+create index idx_mods_trending_score on mods(trending_score desc);
+create index idx_mod_downloads_time_downloaded on mod_downloads(time_downloaded);
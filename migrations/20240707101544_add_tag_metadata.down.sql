@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+alter table mod_tags drop constraint mod_tags_name_key;
+create index idx_mod_tags_name on mod_tags(name);
+
+alter table mod_tags drop column retired_at;
+alter table mod_tags drop column icon;
+alter table mod_tags drop column description;
+alter table mod_tags drop column display_name;
@@ -0,0 +1,12 @@
+-- Add up migration script here
+
+alter table mod_tags add column display_name text;
+alter table mod_tags add column description text;
+alter table mod_tags add column icon text;
+alter table mod_tags add column retired_at timestamptz;
+
+update mod_tags set display_name = initcap(name);
+alter table mod_tags alter column display_name set not null;
+
+drop index idx_mod_tags_name;
+alter table mod_tags add constraint mod_tags_name_key unique (name);
@@ -0,0 +1,16 @@
+-- Add up migration script here
+
+create table mod_download_rollups (
+    mod_version_id integer not null,
+    day date not null,
+    download_count integer not null default 0,
+    primary key (mod_version_id, day),
+    foreign key (mod_version_id) references mod_versions (id) on delete cascade
+);
+
+create index idx_mod_download_rollups_day on mod_download_rollups(day);
+
+insert into mod_download_rollups (mod_version_id, day, download_count)
+select mod_version_id, (time_downloaded at time zone 'utc')::date, count(*)
+from mod_downloads
+group by 1, 2;
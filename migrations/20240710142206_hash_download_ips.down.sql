@@ -0,0 +1,14 @@
+-- Add down migration script here
+
+-- The hashes can't be turned back into addresses, so every row gets a unique placeholder one
+alter table mod_downloads add column ip inet;
+update mod_downloads set ip = '::'::inet + id;
+alter table mod_downloads alter column ip set not null;
+
+drop index if exists idx_mod_downloads_version_ip_hash;
+alter table mod_downloads drop column ip_hash;
+alter table mod_downloads drop constraint mod_downloads_pkey;
+alter table mod_downloads drop column id;
+alter table mod_downloads add primary key (mod_version_id, ip);
+
+drop table if exists download_salts;
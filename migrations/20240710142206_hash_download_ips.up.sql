@@ -0,0 +1,21 @@
+-- Add up migration script here
+
+create table download_salts (
+    id serial primary key not null,
+    salt bytea not null,
+    created_at timestamptz not null default now()
+);
+
+insert into download_salts (salt)
+values (sha256(convert_to(gen_random_uuid()::text || gen_random_uuid()::text, 'UTF8')));
+
+alter table mod_downloads drop constraint mod_downloads_pkey;
+alter table mod_downloads add column id bigserial primary key;
+alter table mod_downloads add column ip_hash bytea;
+
+update mod_downloads
+set ip_hash = sha256((select salt from download_salts) || convert_to(host(ip), 'UTF8'));
+
+alter table mod_downloads drop column ip;
+
+create unique index idx_mod_downloads_version_ip_hash on mod_downloads(mod_version_id, ip_hash);
@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+drop index if exists idx_mod_downloads_version_ip_hash;
+delete from mod_downloads md
+using mod_downloads prev
+where prev.mod_version_id = md.mod_version_id
+and prev.ip_hash = md.ip_hash
+and prev.id < md.id;
+create unique index idx_mod_downloads_version_ip_hash on mod_downloads(mod_version_id, ip_hash);
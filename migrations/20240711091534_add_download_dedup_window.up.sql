@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+drop index if exists idx_mod_downloads_version_ip_hash;
+create index idx_mod_downloads_version_ip_hash on mod_downloads(mod_version_id, ip_hash, time_downloaded);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+drop table if exists mod_download_dimension_rollups;
+drop type if exists download_dimension;
+
+alter table mod_downloads drop column if exists platform;
+alter table mod_downloads drop column if exists gd;
+alter table mod_downloads drop column if exists geode;
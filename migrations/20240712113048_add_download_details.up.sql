@@ -0,0 +1,25 @@
+-- Add up migration script here
+
+alter table mod_downloads add column platform text;
+alter table mod_downloads add column gd text;
+alter table mod_downloads add column geode text;
+
+create type download_dimension as enum ('platform', 'gd', 'geode');
+
+create table mod_download_dimension_rollups (
+    mod_version_id integer not null,
+    day date not null,
+    dimension download_dimension not null,
+    value text not null,
+    download_count integer not null default 0,
+    primary key (mod_version_id, day, dimension, value),
+    foreign key (mod_version_id) references mod_versions (id) on delete cascade
+);
+
+create index idx_mod_download_dimension_rollups_day on mod_download_dimension_rollups(day);
+
+insert into mod_download_dimension_rollups (mod_version_id, day, dimension, value, download_count)
+select md.mod_version_id, (md.time_downloaded at time zone 'utc')::date, d.dimension, 'unknown', count(*)
+from mod_downloads md
+cross join (values ('platform'::download_dimension), ('gd'), ('geode')) d(dimension)
+group by 1, 2, 3;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+alter table mod_gd_versions drop column registered_gd;
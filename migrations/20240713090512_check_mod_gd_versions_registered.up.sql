@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+-- gd is either "*" or a version from the registry, the generated column is null for "*" so it skips the foreign key
+alter table mod_gd_versions add column registered_gd text generated always as (nullif(gd, '*')) stored;
+
+alter table mod_gd_versions add constraint mod_gd_versions_registered_gd_fkey
+    foreign key (registered_gd) references gd_versions(version);
+
+create index idx_mod_gd_versions_registered_gd on mod_gd_versions(registered_gd);
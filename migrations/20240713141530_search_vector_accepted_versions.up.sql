@@ -0,0 +1,26 @@
+-- Add up migration script here
+
+-- only the newest accepted version is indexed, pending and rejected text shouldn't be searchable
+create or replace function update_mod_search_vector(p_mod_id text) returns void as $$
+    update mods m set search_vector =
+        setweight(to_tsvector('english', coalesce(latest.name, '')), 'A') ||
+        setweight(to_tsvector('english', coalesce(latest.description, '')), 'B') ||
+        setweight(to_tsvector('english', coalesce(devs.names, '')), 'B') ||
+        setweight(to_tsvector('english', coalesce(m.about, '')), 'C')
+    from (select p_mod_id as mod_id) target
+    left join (
+        select mv.name, mv.description from mod_versions mv
+        inner join mod_version_statuses mvs on mvs.mod_version_id = mv.id
+        where mv.mod_id = p_mod_id and mvs.status = 'accepted'
+        order by mv.version_key desc nulls last, mv.id desc
+        limit 1
+    ) latest on true, (
+        select string_agg(d.display_name || ' ' || d.username, ' ') as names
+        from mods_developers md
+        inner join developers d on d.id = md.developer_id
+        where md.mod_id = p_mod_id
+    ) devs
+    where m.id = target.mod_id;
+$$ language sql;
+
+select update_mod_search_vector(id) from mods;
@@ -0,0 +1,251 @@
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::Acquire;
+
+use crate::{
+    extractors::auth::Auth,
+    types::{
+        api::{ApiError, ApiResponse},
+        geode_compat::parse_loader_version,
+        models::{
+            collection::{Collection, CollectionEntryPayload},
+            gd_version::GDVersion,
+            mod_gd_version::VerPlatform,
+            mod_version::ModVersionChannel,
+            mod_version_artifact::ModVersionArtifact,
+        },
+    },
+    AppData,
+};
+
+#[derive(Deserialize)]
+struct CollectionPath {
+    id: i32,
+}
+
+#[derive(Deserialize)]
+struct CreateCollectionPayload {
+    name: String,
+    description: Option<String>,
+    #[serde(default)]
+    mods: Vec<CollectionEntryPayload>,
+}
+
+#[derive(Deserialize)]
+struct UpdateCollectionPayload {
+    name: Option<String>,
+    description: Option<String>,
+    mods: Option<Vec<CollectionEntryPayload>>,
+}
+
+#[derive(Deserialize)]
+struct ResolveQuery {
+    platform: VerPlatform,
+    gd: String,
+    geode: String,
+    #[serde(default)]
+    channel: ModVersionChannel,
+}
+
+#[get("/v1/collections/{id}")]
+pub async fn get_one(
+    data: web::Data<AppData>,
+    path: web::Path<CollectionPath>,
+) -> Result<impl Responder, ApiError> {
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    let collection = match Collection::get_one(path.id, &mut pool).await? {
+        None => {
+            return Err(ApiError::NotFound(format!(
+                "Collection {} not found",
+                path.id
+            )))
+        }
+        Some(c) => c,
+    };
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: collection,
+    }))
+}
+
+#[get("/v1/collections/{id}/resolve")]
+pub async fn resolve(
+    data: web::Data<AppData>,
+    path: web::Path<CollectionPath>,
+    query: web::Query<ResolveQuery>,
+) -> Result<impl Responder, ApiError> {
+    if query.platform == VerPlatform::Android || query.platform == VerPlatform::Mac {
+        return Err(ApiError::BadRequest("Invalid platform. Use android32 / android64 for android and mac-intel / mac-arm for mac".to_string()));
+    }
+    let geode = match parse_loader_version(&query.geode) {
+        None => {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid geode version {}",
+                query.geode
+            )))
+        }
+        Some(v) => v,
+    };
+
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+    GDVersion::validate(&query.gd, &mut pool).await?;
+
+    let collection = match Collection::get_one(path.id, &mut pool).await? {
+        None => {
+            return Err(ApiError::NotFound(format!(
+                "Collection {} not found",
+                path.id
+            )))
+        }
+        Some(c) => c,
+    };
+
+    let mut resolved = collection
+        .resolve(query.platform, &query.gd, &geode, query.channel, &mut pool)
+        .await?;
+
+    let ids: Vec<i32> = resolved.mods.iter().map(|x| x.version.id).collect();
+    let mut artifacts =
+        ModVersionArtifact::get_for_mod_versions(&ids, query.platform, &mut pool).await?;
+    for i in &mut resolved.mods {
+        i.version.modify_download_link(&data.app_url);
+        if let Some(artifact) = artifacts.remove(&i.version.id) {
+            i.version.use_artifact(artifact, &data.app_url);
+        }
+    }
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: resolved,
+    }))
+}
+
+#[get("/v1/me/collections")]
+pub async fn get_own(data: web::Data<AppData>, auth: Auth) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    let collections = Collection::get_for_developer(dev.id, &mut pool).await?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: collections,
+    }))
+}
+
+#[post("/v1/collections")]
+pub async fn create(
+    data: web::Data<AppData>,
+    payload: web::Json<CreateCollectionPayload>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
+    let result = match Collection::create(
+        dev.id,
+        &payload.name,
+        payload.description.as_deref(),
+        &payload.mods,
+        &mut transaction,
+    )
+    .await
+    {
+        Err(e) => {
+            transaction
+                .rollback()
+                .await
+                .or(Err(ApiError::TransactionError))?;
+            return Err(e);
+        }
+        Ok(r) => r,
+    };
+    transaction
+        .commit()
+        .await
+        .or(Err(ApiError::TransactionError))?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: result,
+    }))
+}
+
+#[put("/v1/collections/{id}")]
+pub async fn update(
+    data: web::Data<AppData>,
+    path: web::Path<CollectionPath>,
+    payload: web::Json<UpdateCollectionPayload>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    match Collection::get_one(path.id, &mut pool).await? {
+        None => {
+            return Err(ApiError::NotFound(format!(
+                "Collection {} not found",
+                path.id
+            )))
+        }
+        Some(c) if c.developer.id != dev.id && !dev.admin => return Err(ApiError::Forbidden),
+        Some(_) => {}
+    };
+
+    let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
+    let result = match Collection::update(
+        path.id,
+        payload.name.as_deref(),
+        payload.description.as_deref(),
+        payload.mods.as_deref(),
+        &mut transaction,
+    )
+    .await
+    {
+        Err(e) => {
+            transaction
+                .rollback()
+                .await
+                .or(Err(ApiError::TransactionError))?;
+            return Err(e);
+        }
+        Ok(r) => r,
+    };
+    transaction
+        .commit()
+        .await
+        .or(Err(ApiError::TransactionError))?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: result,
+    }))
+}
+
+#[delete("/v1/collections/{id}")]
+pub async fn delete(
+    data: web::Data<AppData>,
+    path: web::Path<CollectionPath>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    match Collection::get_one(path.id, &mut pool).await? {
+        None => {
+            return Err(ApiError::NotFound(format!(
+                "Collection {} not found",
+                path.id
+            )))
+        }
+        Some(c) if c.developer.id != dev.id && !dev.admin => return Err(ApiError::Forbidden),
+        Some(_) => {}
+    };
+
+    Collection::delete(path.id, &mut pool).await?;
+
+    Ok(HttpResponse::NoContent())
+}
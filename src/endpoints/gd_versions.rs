@@ -0,0 +1,126 @@
+use actix_web::{get, post, put, web, Responder};
+use serde::Deserialize;
+use sqlx::Acquire;
+
+use crate::{
+    extractors::auth::Auth,
+    types::{
+        api::{ApiError, ApiResponse},
+        models::{gd_version::GDVersion, mod_gd_version::VerPlatform},
+    },
+    AppData,
+};
+
+#[derive(Deserialize)]
+struct CreateGDVersionPayload {
+    version: String,
+    #[serde(default)]
+    current: bool,
+    #[serde(default)]
+    deprecated: bool,
+    platforms: Vec<VerPlatform>,
+}
+
+#[derive(Deserialize)]
+struct UpdateGDVersionPayload {
+    current: Option<bool>,
+    deprecated: Option<bool>,
+    platforms: Option<Vec<VerPlatform>>,
+}
+
+#[get("/v1/gd-versions")]
+pub async fn index(data: web::Data<AppData>) -> Result<impl Responder, ApiError> {
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    let versions = GDVersion::get_all(&mut pool).await?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: versions,
+    }))
+}
+
+#[post("/v1/gd-versions")]
+pub async fn create(
+    data: web::Data<AppData>,
+    payload: web::Json<CreateGDVersionPayload>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    if !dev.admin {
+        return Err(ApiError::Forbidden);
+    }
+
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+    let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
+    let result = match GDVersion::create(
+        &payload.version,
+        payload.current,
+        payload.deprecated,
+        &payload.platforms,
+        &mut transaction,
+    )
+    .await
+    {
+        Err(e) => {
+            transaction
+                .rollback()
+                .await
+                .or(Err(ApiError::TransactionError))?;
+            return Err(e);
+        }
+        Ok(r) => r,
+    };
+    transaction
+        .commit()
+        .await
+        .or(Err(ApiError::TransactionError))?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: result,
+    }))
+}
+
+#[put("/v1/gd-versions/{version}")]
+pub async fn update(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+    payload: web::Json<UpdateGDVersionPayload>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    if !dev.admin {
+        return Err(ApiError::Forbidden);
+    }
+
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+    let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
+    let result = match GDVersion::update(
+        &path,
+        payload.current,
+        payload.deprecated,
+        payload.platforms.as_deref(),
+        &mut transaction,
+    )
+    .await
+    {
+        Err(e) => {
+            transaction
+                .rollback()
+                .await
+                .or(Err(ApiError::TransactionError))?;
+            return Err(e);
+        }
+        Ok(r) => r,
+    };
+    transaction
+        .commit()
+        .await
+        .or(Err(ApiError::TransactionError))?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: result,
+    }))
+}
@@ -0,0 +1,151 @@
+use actix_web::{post, web, Responder};
+use serde::Deserialize;
+
+use crate::{
+    types::{
+        api::{ApiError, ApiResponse},
+        geode_compat::parse_loader_version,
+        models::{
+            collection::Collection,
+            gd_version::GDVersion,
+            lockfile::{Lockfile, MAX_LOCKFILE_MODS},
+            mod_gd_version::VerPlatform,
+            mod_version::{ModVersion, ModVersionChannel},
+        },
+    },
+    AppData,
+};
+
+#[derive(Deserialize)]
+struct CreateLockfilePayload {
+    #[serde(default)]
+    mods: Vec<String>,
+    collection: Option<i32>,
+    platform: VerPlatform,
+    gd: String,
+    geode: String,
+    #[serde(default)]
+    channel: ModVersionChannel,
+}
+
+#[post("/v1/lockfiles")]
+pub async fn create(
+    data: web::Data<AppData>,
+    payload: web::Json<CreateLockfilePayload>,
+) -> Result<impl Responder, ApiError> {
+    if payload.platform == VerPlatform::Android || payload.platform == VerPlatform::Mac {
+        return Err(ApiError::BadRequest("Invalid platform. Use android32 / android64 for android and mac-intel / mac-arm for mac".to_string()));
+    }
+    if payload.mods.is_empty() == payload.collection.is_none() {
+        return Err(ApiError::BadRequest(
+            "Pass either a list of mods or a collection".to_string(),
+        ));
+    }
+    if payload.mods.len() > MAX_LOCKFILE_MODS {
+        return Err(ApiError::BadRequest(format!(
+            "Lockfiles can have at most {} mods",
+            MAX_LOCKFILE_MODS
+        )));
+    }
+    let geode = match parse_loader_version(&payload.geode) {
+        Some(g) => g,
+        None => {
+            return Err(ApiError::BadRequest(
+                "Invalid geode version format".to_string(),
+            ));
+        }
+    };
+
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+    GDVersion::validate(&payload.gd, &mut pool).await?;
+
+    let (versions, unresolved) = match payload.collection {
+        Some(id) => {
+            let collection = match Collection::get_one(id, &mut pool).await? {
+                None => return Err(ApiError::NotFound(format!("Collection {} not found", id))),
+                Some(c) => c,
+            };
+            let resolved = collection
+                .resolve(
+                    payload.platform,
+                    &payload.gd,
+                    &geode,
+                    payload.channel,
+                    &mut pool,
+                )
+                .await?;
+            (
+                resolved.mods.into_iter().map(|x| x.version).collect(),
+                resolved.unresolved,
+            )
+        }
+        None => {
+            // Listing a mod twice would look for it twice
+            let mut mods = payload.mods.clone();
+            mods.sort();
+            mods.dedup();
+            let mut latest = ModVersion::get_latest_for_mods(
+                &mut pool,
+                mods.clone(),
+                Some(payload.gd.clone()),
+                vec![payload.platform],
+                Some(&geode.to_string()),
+                payload.channel,
+            )
+            .await?;
+            let mut versions: Vec<ModVersion> = vec![];
+            let mut unresolved: Vec<String> = vec![];
+            for id in mods {
+                match latest.remove(&id) {
+                    Some(v) => versions.push(v),
+                    None => unresolved.push(id),
+                }
+            }
+            (versions, unresolved)
+        }
+    };
+
+    if !unresolved.is_empty() {
+        return Err(ApiError::BadRequest(format!(
+            "No compatible version found for {}",
+            unresolved.join(", ")
+        )));
+    }
+
+    let lockfile = Lockfile::create(
+        versions,
+        payload.platform,
+        &payload.gd,
+        &geode,
+        payload.channel,
+        &data.app_url,
+        &mut pool,
+    )
+    .await?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: lockfile,
+    }))
+}
+
+#[post("/v1/lockfiles/verify")]
+pub async fn verify(
+    data: web::Data<AppData>,
+    payload: web::Json<Lockfile>,
+) -> Result<impl Responder, ApiError> {
+    if payload.mods.len() > MAX_LOCKFILE_MODS {
+        return Err(ApiError::BadRequest(format!(
+            "Lockfiles can have at most {} mods",
+            MAX_LOCKFILE_MODS
+        )));
+    }
+
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+    let result = payload.verify(&mut pool).await?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: result,
+    }))
+}
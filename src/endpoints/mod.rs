@@ -1,5 +1,8 @@
 pub mod auth;
+pub mod collections;
 pub mod developers;
+pub mod gd_versions;
+pub mod lockfiles;
 pub mod mod_versions;
 pub mod mods;
 pub mod tags;
@@ -1,21 +1,25 @@
-use std::str::FromStr;
-
-use actix_web::{dev::ConnectionInfo, get, post, put, web, HttpResponse, Responder};
+use actix_web::{delete, dev::ConnectionInfo, get, post, put, web, HttpResponse, Responder};
 use serde::Deserialize;
 use sqlx::{types::ipnetwork::IpNetwork, Acquire};
+use std::str::FromStr;
 
 use crate::{
     extractors::auth::Auth,
     types::{
-        api::{ApiError, ApiResponse},
+        api::{upload_response, ApiError, ApiResponse},
+        geode_compat::parse_loader_version,
         mod_json::{split_version_and_compare, ModJson},
         models::{
             developer::Developer,
-            download,
+            download::DownloadDetails,
+            gd_version::GDVersion,
             mod_entity::{download_geode_file, Mod},
-            mod_gd_version::{GDVersionEnum, VerPlatform},
-            mod_version::{self, ModVersion},
-            mod_version_status::ModVersionStatusEnum,
+            mod_gd_version::VerPlatform,
+            mod_version::{self, ModVersion, ModVersionChannel},
+            mod_version_artifact::{ModVersionArtifact, ModVersionArtifactUpload},
+            mod_version_diff::ModVersionDiff,
+            mod_version_status::{ModVersionStatus, ModVersionStatusEnum},
+            tag::Tag,
         },
     },
     AppData,
@@ -35,6 +39,8 @@ pub struct GetOnePath {
 #[derive(Deserialize)]
 pub struct CreateQueryParams {
     download_link: String,
+    #[serde(default)]
+    artifacts: Vec<ModVersionArtifactUpload>,
 }
 
 #[derive(Deserialize)]
@@ -54,19 +60,30 @@ struct UpdateVersionPath {
     version: String,
 }
 
+#[derive(Deserialize)]
+struct DiffPath {
+    id: String,
+    from: String,
+    to: String,
+}
+
 #[derive(Deserialize)]
 struct GetOneQuery {
     platforms: Option<String>,
+    platform: Option<VerPlatform>,
     gd: Option<String>,
     major: Option<u32>,
+    #[serde(default)]
+    channel: ModVersionChannel,
 }
 
 #[derive(Deserialize)]
 struct IndexQuery {
     page: Option<i64>,
     per_page: Option<i64>,
+    cursor: Option<String>,
     #[serde(default)]
-    gd: Option<GDVersionEnum>,
+    gd: Option<String>,
     platforms: Option<String>,
     status: Option<ModVersionStatusEnum>,
     compare: Option<String>,
@@ -92,15 +109,20 @@ pub async fn get_version_index(
 
     let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
 
+    if let Some(gd) = &query.gd {
+        GDVersion::validate(gd, &mut pool).await?;
+    }
+
     let mut result = ModVersion::get_index(
         mod_version::IndexQuery {
             mod_id: path.id.clone(),
             page: query.page.unwrap_or(1),
             per_page: query.per_page.unwrap_or(10),
             compare,
-            gd: query.gd,
+            gd: query.gd.clone(),
             platforms,
             status: query.status.unwrap_or(ModVersionStatusEnum::Accepted),
+            cursor: query.cursor.clone(),
         },
         &mut pool,
     )
@@ -125,36 +147,79 @@ pub async fn get_one(
 
     let mut version = {
         if path.version == "latest" {
-            let gd: Option<GDVersionEnum> = match query.gd {
-                Some(ref gd) => Some(
-                    GDVersionEnum::from_str(gd)
-                        .or(Err(ApiError::BadRequest("Invalid gd".to_string())))?,
-                ),
-                None => None,
-            };
+            if let Some(gd) = &query.gd {
+                GDVersion::validate(gd, &mut pool).await?;
+            }
 
             let platform_string = query.platforms.clone().unwrap_or_default();
-            let platforms = VerPlatform::parse_query_string(&platform_string);
+            let mut platforms = VerPlatform::parse_query_string(&platform_string);
+            if platforms.is_empty() {
+                platforms.extend(query.platform);
+            }
 
-            ModVersion::get_latest_for_mod(&path.id, gd, platforms, query.major, &mut pool).await?
+            ModVersion::get_latest_for_mod(
+                &path.id,
+                query.gd.clone(),
+                platforms,
+                query.major,
+                query.channel,
+                &mut pool,
+            )
+            .await?
         } else {
             ModVersion::get_one(&path.id, &path.version, true, false, &mut pool).await?
         }
     };
 
     version.modify_download_link(&data.app_url);
+    if let Some(platform) = query.platform {
+        if let Some(artifact) =
+            ModVersionArtifact::get_for_mod_version(version.id, platform, &mut pool).await?
+        {
+            version.use_artifact(artifact, &data.app_url);
+        }
+    }
     Ok(web::Json(ApiResponse {
         error: "".to_string(),
         payload: version,
     }))
 }
 
+#[get("v1/mods/{id}/versions/{from}/diff/{to}")]
+pub async fn get_diff(
+    path: web::Path<DiffPath>,
+    data: web::Data<AppData>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    // Developers of the mod and admins can also compare versions that aren't accepted yet
+    let only_accepted = match auth.developer() {
+        Ok(dev) => !dev.admin && !Developer::has_access_to_mod(dev.id, &path.id, &mut pool).await?,
+        Err(_) => true,
+    };
+
+    let diff =
+        ModVersionDiff::get(&path.id, &path.from, &path.to, only_accepted, &mut pool).await?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: diff,
+    }))
+}
+
 #[derive(Deserialize)]
 struct DownloadQuery {
-    gd: Option<GDVersionEnum>,
+    gd: Option<String>,
     // platform1,platform2,...
     platforms: Option<String>,
+    // picks the package for this platform, if the version has one
+    platform: Option<VerPlatform>,
     major: Option<u32>,
+    #[serde(default)]
+    channel: ModVersionChannel,
+    // loader version of the client, only used for stats
+    geode: Option<String>,
 }
 
 #[get("v1/mods/{id}/versions/{version}/download")]
@@ -167,15 +232,37 @@ pub async fn download_version(
     let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
     let mod_version = {
         if path.version == "latest" {
+            if let Some(gd) = &query.gd {
+                GDVersion::validate(gd, &mut pool).await?;
+            }
             let platform_str = query.platforms.clone().unwrap_or_default();
-            let platforms = VerPlatform::parse_query_string(&platform_str);
-            ModVersion::get_latest_for_mod(&path.id, query.gd, platforms, query.major, &mut pool)
-                .await?
+            let mut platforms = VerPlatform::parse_query_string(&platform_str);
+            if platforms.is_empty() {
+                platforms.extend(query.platform);
+            }
+            ModVersion::get_latest_for_mod(
+                &path.id,
+                query.gd.clone(),
+                platforms,
+                query.major,
+                query.channel,
+                &mut pool,
+            )
+            .await?
         } else {
             ModVersion::get_one(&path.id, &path.version, false, false, &mut pool).await?
         }
     };
-    let url = mod_version.download_link;
+    let artifact = match query.platform {
+        Some(platform) => {
+            ModVersionArtifact::get_for_mod_version(mod_version.id, platform, &mut pool).await?
+        }
+        None => None,
+    };
+    let url = match artifact {
+        Some(a) => a.download_link,
+        None => mod_version.download_link,
+    };
 
     let ip = match info.realip_remote_addr() {
         None => return Err(ApiError::InternalError),
@@ -183,27 +270,27 @@ pub async fn download_version(
     };
     let net: IpNetwork = ip.parse().or(Err(ApiError::InternalError))?;
 
-    if download::create_download(net, mod_version.id, &mut pool).await? {
-        let name = mod_version.mod_id.clone();
-        let version = mod_version.version.clone();
-        tokio::spawn(async move {
-            if let Err(e) = ModVersion::calculate_cached_downloads(mod_version.id, &mut pool).await
-            {
-                log::error!(
-                    "Failed to calculate cached downloads for mod version {}. Error: {}",
-                    version,
-                    e
-                );
-            }
-            if let Err(e) = Mod::calculate_cached_downloads(&mod_version.mod_id, &mut pool).await {
-                log::error!(
-                    "Failed to calculate cached downloads for mod {}. Error: {}",
-                    name,
-                    e
-                );
-            }
-        });
-    }
+    // A download only counts towards a platform when the client asked for exactly one
+    let platform = query.platform.or_else(|| {
+        match query.platforms.as_deref()?.split(',').collect::<Vec<_>>()[..] {
+            [p] => VerPlatform::from_str(p.trim()).ok(),
+            _ => None,
+        }
+    });
+    data.downloads.record(
+        net,
+        mod_version.id,
+        &mod_version.mod_id,
+        DownloadDetails {
+            platform,
+            gd: query.gd.clone(),
+            geode: query
+                .geode
+                .as_deref()
+                .and_then(parse_loader_version)
+                .map(|v| v.to_string()),
+        },
+    );
 
     Ok(HttpResponse::Found()
         .append_header(("Location", url))
@@ -229,7 +316,7 @@ pub async fn create_version(
     }
 
     let mut file_path = download_geode_file(&payload.download_link).await?;
-    let json = ModJson::from_zip(&mut file_path, &payload.download_link, dev.verified)
+    let mut json = ModJson::from_zip(&mut file_path, &payload.download_link, dev.verified)
         .or(Err(ApiError::FilesystemError))?;
     if json.id != path.id {
         return Err(ApiError::BadRequest(format!(
@@ -237,7 +324,9 @@ pub async fn create_version(
             path.id, json.id
         )));
     }
+    json.add_artifacts(&payload.artifacts).await?;
     json.validate()?;
+    let warnings = Tag::get_upload_warnings(json.tags.as_deref().unwrap_or(&[]), &mut pool).await?;
     let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
     if let Err(e) = Mod::new_version(&json, dev, &mut transaction).await {
         transaction
@@ -250,7 +339,7 @@ pub async fn create_version(
         .commit()
         .await
         .or(Err(ApiError::TransactionError))?;
-    Ok(HttpResponse::NoContent())
+    Ok(upload_response(warnings))
 }
 
 #[put("v1/mods/{id}/versions/{version}")]
@@ -305,3 +394,135 @@ pub async fn update_version(
 
     Ok(HttpResponse::NoContent())
 }
+
+#[get("v1/mods/{id}/versions/{version}/history")]
+pub async fn get_status_history(
+    path: web::Path<UpdateVersionPath>,
+    data: web::Data<AppData>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+    if !dev.admin && !(Developer::has_access_to_mod(dev.id, &path.id, &mut pool).await?) {
+        return Err(ApiError::Forbidden);
+    }
+
+    let history =
+        ModVersionStatus::get_history(&path.id, path.version.trim_start_matches('v'), &mut pool)
+            .await?;
+    if history.is_empty() {
+        return Err(ApiError::NotFound(format!(
+            "Version {} of mod {} not found",
+            path.version, path.id
+        )));
+    }
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: history,
+    }))
+}
+
+#[put("v1/mods/{id}/versions/{version}/artifact")]
+pub async fn replace_version(
+    path: web::Path<UpdateVersionPath>,
+    data: web::Data<AppData>,
+    payload: web::Json<CreateQueryParams>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    if !(Developer::owns_mod(dev.id, &path.id, &mut pool).await?) {
+        return Err(ApiError::Forbidden);
+    }
+
+    let version = ModVersion::get_one(
+        &path.id,
+        path.version.trim_start_matches('v'),
+        false,
+        false,
+        &mut pool,
+    )
+    .await?;
+    if version.status != ModVersionStatusEnum::Pending {
+        return Err(ApiError::BadRequest(
+            "Only pending versions can be replaced".to_string(),
+        ));
+    }
+
+    let mut file_path = download_geode_file(&payload.download_link).await?;
+    let mut json = ModJson::from_zip(&mut file_path, &payload.download_link, dev.verified)
+        .or(Err(ApiError::FilesystemError))?;
+    if json.id != path.id {
+        return Err(ApiError::BadRequest(format!(
+            "Request id {} does not match mod.json id {}",
+            path.id, json.id
+        )));
+    }
+    if json.version.trim_start_matches('v') != version.version.trim_start_matches('v') {
+        return Err(ApiError::BadRequest(format!(
+            "mod.json version {} does not match the version being replaced {}",
+            json.version, version.version
+        )));
+    }
+    json.add_artifacts(&payload.artifacts).await?;
+    json.validate()?;
+    let warnings = Tag::get_upload_warnings(json.tags.as_deref().unwrap_or(&[]), &mut pool).await?;
+
+    let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
+    if let Err(e) = Mod::replace_pending_version(&json, version.id, dev, &mut transaction).await {
+        transaction
+            .rollback()
+            .await
+            .or(Err(ApiError::TransactionError))?;
+        return Err(e);
+    }
+    transaction
+        .commit()
+        .await
+        .or(Err(ApiError::TransactionError))?;
+    Ok(upload_response(warnings))
+}
+
+#[delete("v1/mods/{id}/versions/{version}")]
+pub async fn withdraw_version(
+    path: web::Path<UpdateVersionPath>,
+    data: web::Data<AppData>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    if !(Developer::owns_mod(dev.id, &path.id, &mut pool).await?) {
+        return Err(ApiError::Forbidden);
+    }
+
+    let version = ModVersion::get_one(
+        &path.id,
+        path.version.trim_start_matches('v'),
+        false,
+        false,
+        &mut pool,
+    )
+    .await?;
+    if version.status != ModVersionStatusEnum::Pending {
+        return Err(ApiError::BadRequest(
+            "Only pending versions can be withdrawn".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
+    if let Err(e) = ModVersion::withdraw(version.id, dev.id, &mut transaction).await {
+        transaction
+            .rollback()
+            .await
+            .or(Err(ApiError::TransactionError))?;
+        return Err(e);
+    }
+    transaction
+        .commit()
+        .await
+        .or(Err(ApiError::TransactionError))?;
+    Ok(HttpResponse::NoContent())
+}
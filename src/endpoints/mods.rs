@@ -1,17 +1,28 @@
-use actix_web::{get, post, put, web, HttpResponse, Responder};
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
 use serde::Deserialize;
 use sqlx::Acquire;
+use std::collections::HashSet;
 
 use crate::extractors::auth::Auth;
-use crate::types::api::{create_download_link, ApiError, ApiResponse};
+use crate::types::api::{
+    create_download_link, create_platform_download_link, upload_response, ApiError, ApiResponse,
+};
+use crate::types::geode_compat::parse_loader_version;
 use crate::types::mod_json::ModJson;
+use crate::types::models::dependency::DependencyImportance;
+use crate::types::models::developer::Developer;
+use crate::types::models::gd_version::GDVersion;
 use crate::types::models::incompatibility::Incompatibility;
+use crate::types::models::mod_deprecation::ModDeprecation;
 use crate::types::models::mod_entity::{download_geode_file, Mod, ModUpdate};
-use crate::types::models::mod_gd_version::{GDVersionEnum, VerPlatform};
+use crate::types::models::mod_gd_version::VerPlatform;
+use crate::types::models::mod_version::ModVersionChannel;
+use crate::types::models::mod_version_artifact::{ModVersionArtifact, ModVersionArtifactUpload};
 use crate::types::models::mod_version_status::ModVersionStatusEnum;
+use crate::types::models::tag::Tag;
 use crate::AppData;
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum IndexSortType {
     #[default]
@@ -20,15 +31,26 @@ pub enum IndexSortType {
     RecentlyPublished,
     Name,
     NameReverse,
+    Relevance,
+    Trending,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TagsMode {
+    #[default]
+    Any,
+    All,
 }
 
 #[derive(Deserialize)]
 pub struct IndexQueryParams {
     pub page: Option<i64>,
     pub per_page: Option<i64>,
+    pub cursor: Option<String>,
     pub query: Option<String>,
     #[serde(default)]
-    pub gd: Option<GDVersionEnum>,
+    pub gd: Option<String>,
     #[serde(default)]
     pub platforms: Option<String>,
     #[serde(default)]
@@ -36,13 +58,38 @@ pub struct IndexQueryParams {
     pub geode: Option<String>,
     pub developer: Option<String>,
     pub tags: Option<String>,
+    #[serde(default)]
+    pub tags_mode: TagsMode,
+    pub exclude_tags: Option<String>,
     pub featured: Option<bool>,
+    pub deprecated: Option<bool>,
     pub status: Option<ModVersionStatusEnum>,
+    pub facets: Option<String>,
+    pub depends_on: Option<String>,
+    pub depends_on_importance: Option<DependencyImportance>,
+    pub incompatible_with: Option<String>,
+    #[serde(default)]
+    pub channel: ModVersionChannel,
+}
+
+#[derive(Deserialize)]
+struct SuggestQueryParams {
+    q: String,
+    limit: Option<i64>,
 }
 
+#[derive(Deserialize)]
+struct BatchPayload {
+    ids: Vec<String>,
+}
+
+const MAX_BATCH_SIZE: usize = 100;
+
 #[derive(Deserialize)]
 pub struct CreateQueryParams {
     download_link: String,
+    #[serde(default)]
+    artifacts: Vec<ModVersionArtifactUpload>,
 }
 
 #[get("/v1/mods")]
@@ -62,8 +109,12 @@ pub async fn index(
         }
     }
 
+    if let Some(gd) = &query.gd {
+        GDVersion::validate(gd, &mut pool).await?;
+    }
+
     let mut result = Mod::get_index(&mut pool, query.0).await?;
-    for i in &mut result.data {
+    for i in &mut result.page.data {
         for j in &mut i.versions {
             j.modify_download_link(&data.app_url);
         }
@@ -74,6 +125,28 @@ pub async fn index(
     }))
 }
 
+#[get("/v1/mods/suggest")]
+pub async fn suggest(
+    data: web::Data<AppData>,
+    query: web::Query<SuggestQueryParams>,
+) -> Result<impl Responder, ApiError> {
+    let q = query.q.trim();
+    if q.is_empty() {
+        return Ok(web::Json(ApiResponse {
+            error: "".into(),
+            payload: vec![],
+        }));
+    }
+    let limit = query.limit.unwrap_or(10).clamp(1, 25);
+
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+    let result = Mod::suggest(q, limit, &mut pool).await?;
+    Ok(web::Json(ApiResponse {
+        error: "".into(),
+        payload: result,
+    }))
+}
+
 #[get("/v1/mods/{id}")]
 pub async fn get(
     data: web::Data<AppData>,
@@ -95,6 +168,40 @@ pub async fn get(
     }
 }
 
+#[post("/v1/mods/batch")]
+pub async fn get_batch(
+    data: web::Data<AppData>,
+    payload: web::Json<BatchPayload>,
+) -> Result<impl Responder, ApiError> {
+    if payload.ids.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::BadRequest(format!(
+            "Can't fetch more than {} mods at once",
+            MAX_BATCH_SIZE
+        )));
+    }
+    let mut seen: HashSet<&str> = HashSet::new();
+    let ids: Vec<String> = payload
+        .ids
+        .iter()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty() && seen.insert(x))
+        .map(String::from)
+        .collect();
+
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+    let mut mods = Mod::get_many(&ids, false, &mut pool).await?;
+    for m in &mut mods {
+        for i in &mut m.versions {
+            i.modify_download_link(&data.app_url);
+        }
+    }
+
+    Ok(web::Json(ApiResponse {
+        error: "".into(),
+        payload: mods,
+    }))
+}
+
 #[post("/v1/mods")]
 pub async fn create(
     data: web::Data<AppData>,
@@ -104,8 +211,10 @@ pub async fn create(
     let dev = auth.developer()?;
     let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
     let mut file_path = download_geode_file(&payload.download_link).await?;
-    let json = ModJson::from_zip(&mut file_path, &payload.download_link, dev.verified)?;
+    let mut json = ModJson::from_zip(&mut file_path, &payload.download_link, dev.verified)?;
+    json.add_artifacts(&payload.artifacts).await?;
     json.validate()?;
+    let warnings = Tag::get_upload_warnings(json.tags.as_deref().unwrap_or(&[]), &mut pool).await?;
     let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
     let result = Mod::from_json(&json, dev, &mut transaction).await;
     if result.is_err() {
@@ -119,15 +228,17 @@ pub async fn create(
         .commit()
         .await
         .or(Err(ApiError::TransactionError))?;
-    Ok(HttpResponse::NoContent())
+    Ok(upload_response(warnings))
 }
 
 #[derive(Deserialize)]
 struct UpdateQueryParams {
     ids: String,
-    gd: GDVersionEnum,
+    gd: String,
     platform: VerPlatform,
     geode: String,
+    #[serde(default)]
+    channel: ModVersionChannel,
 }
 #[get("/v1/mods/updates")]
 pub async fn get_mod_updates(
@@ -146,47 +257,100 @@ pub async fn get_mod_updates(
         .map(String::from)
         .collect::<Vec<String>>();
 
-    let geode = match semver::Version::parse(&query.geode) {
-        Ok(g) => g,
-        Err(e) => {
-            log::error!("{}", e);
+    let geode = match parse_loader_version(&query.geode) {
+        Some(g) => g,
+        None => {
             return Err(ApiError::BadRequest(
                 "Invalid geode version format".to_string(),
             ));
         }
     };
 
-    let mut result: Vec<ModUpdate> =
-        Mod::get_updates(&ids, query.platform, &geode, query.gd, &mut pool).await?;
-    let mut replacements =
-        Incompatibility::get_supersedes_for(&ids, query.platform, query.gd, &geode, &mut pool)
-            .await?;
+    GDVersion::validate(&query.gd, &mut pool).await?;
+
+    let mut result: Vec<ModUpdate> = Mod::get_updates(
+        &ids,
+        query.platform,
+        &geode,
+        &query.gd,
+        query.channel,
+        &mut pool,
+    )
+    .await?;
+    let mut replacements = Incompatibility::get_supersedes_for(
+        &ids,
+        query.platform,
+        &query.gd,
+        &geode,
+        query.channel,
+        &mut pool,
+    )
+    .await?;
+
+    let mut version_ids: Vec<i32> = result.iter().map(|x| x.mod_version_id).collect();
+    version_ids.extend(replacements.values().map(|x| x.replacement_id));
+    let artifacts =
+        ModVersionArtifact::get_for_mod_versions(&version_ids, query.platform, &mut pool).await?;
+    let link_for = |id: &str, version: &str, mod_version_id: i32| {
+        if artifacts.contains_key(&mod_version_id) {
+            create_platform_download_link(&data.app_url, id, version, query.platform)
+        } else {
+            create_download_link(&data.app_url, id, version)
+        }
+    };
 
     for i in &mut result {
         if let Some(replacement) = replacements.get(&i.id) {
             let mut clone = replacement.clone();
-            clone.download_link = create_download_link(&data.app_url, &clone.id, &clone.version);
+            clone.download_link = link_for(&clone.id, &clone.version, clone.replacement_id);
             i.replacement = Some(clone);
             replacements.remove_entry(&i.id);
         }
-        i.download_link = create_download_link(&data.app_url, &i.id, &i.version);
+        if let Some(version) = &i.version {
+            i.download_link = Some(link_for(&i.id, version, i.mod_version_id));
+        }
     }
 
     for i in replacements {
         let mut replacement = i.1.clone();
-        replacement.download_link =
-            create_download_link(&data.app_url, &replacement.id, &replacement.version);
+        replacement.download_link = link_for(
+            &replacement.id,
+            &replacement.version,
+            replacement.replacement_id,
+        );
         result.push(ModUpdate {
             id: i.0.clone(),
-            version: "1.0.0".to_string(),
+            version: Some("1.0.0".to_string()),
             mod_version_id: 0,
-            download_link: replacement.download_link.clone(),
+            download_link: Some(replacement.download_link.clone()),
             replacement: Some(replacement),
+            deprecation: None,
             dependencies: vec![],
             incompatibilities: vec![],
         });
     }
 
+    let mut deprecations = ModDeprecation::get_for_mods(&ids, &mut pool).await?;
+    for i in &mut result {
+        i.deprecation = deprecations.remove(&i.id);
+    }
+
+    // Deprecated mods without a compatible version are still reported, without a version to update to
+    for id in &ids {
+        if let Some(deprecation) = deprecations.remove(id) {
+            result.push(ModUpdate {
+                id: id.clone(),
+                version: None,
+                mod_version_id: 0,
+                download_link: None,
+                replacement: None,
+                deprecation: Some(deprecation),
+                dependencies: vec![],
+                incompatibilities: vec![],
+            });
+        }
+    }
+
     Ok(web::Json(ApiResponse {
         error: "".into(),
         payload: result,
@@ -244,3 +408,77 @@ pub async fn update_mod(
 
     Ok(HttpResponse::NoContent())
 }
+
+#[derive(Deserialize)]
+struct DeprecateModPayload {
+    reason: String,
+    successor: Option<String>,
+}
+
+#[put("/v1/mods/{id}/deprecation")]
+pub async fn deprecate_mod(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+    payload: web::Json<DeprecateModPayload>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+    if !dev.admin && !Developer::owns_mod(dev.id, &path, &mut pool).await? {
+        return Err(ApiError::Forbidden);
+    }
+    if Mod::get_one(&path, false, &mut pool).await?.is_none() {
+        return Err(ApiError::NotFound(format!("Mod {} not found", path)));
+    }
+
+    let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
+    if let Err(e) = ModDeprecation::set_for_mod(
+        &path,
+        &payload.reason,
+        payload.successor.as_deref(),
+        dev.id,
+        &mut transaction,
+    )
+    .await
+    {
+        transaction
+            .rollback()
+            .await
+            .or(Err(ApiError::TransactionError))?;
+        return Err(e);
+    }
+    transaction
+        .commit()
+        .await
+        .or(Err(ApiError::TransactionError))?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[delete("/v1/mods/{id}/deprecation")]
+pub async fn undeprecate_mod(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+    if !dev.admin && !Developer::owns_mod(dev.id, &path, &mut pool).await? {
+        return Err(ApiError::Forbidden);
+    }
+
+    let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
+    if let Err(e) = ModDeprecation::delete_for_mod(&path, &mut transaction).await {
+        transaction
+            .rollback()
+            .await
+            .or(Err(ApiError::TransactionError))?;
+        return Err(e);
+    }
+    transaction
+        .commit()
+        .await
+        .or(Err(ApiError::TransactionError))?;
+
+    Ok(HttpResponse::NoContent())
+}
@@ -1,12 +1,123 @@
-use actix_web::{get, web, Responder};
-
-use crate::{types::{api::{ApiError, ApiResponse}, models::stats::Stats}, AppData};
-
-#[get("/v1/stats")]
-pub async fn get_stats(data: web::Data<AppData>) -> Result<impl Responder, ApiError> {
-    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
-    Ok(web::Json(ApiResponse {
-        error: "".into(),
-        payload: Stats::get_cached(&mut pool).await?,
-    }))
-}
+use actix_web::{get, web, Responder};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::{
+    extractors::auth::Auth,
+    types::{
+        api::{ApiError, ApiResponse},
+        models::{
+            developer::Developer,
+            developer_stats::DeveloperStats,
+            download_stats::{ModDownloadStats, StatsGranularity},
+            mod_entity::Mod,
+            stats::Stats,
+        },
+    },
+    AppData,
+};
+
+/// Longest range the download stats can be asked for, in days
+const MAX_STATS_RANGE_DAYS: i64 = 731;
+
+#[derive(Deserialize)]
+struct ModStatsQuery {
+    from: Option<String>,
+    to: Option<String>,
+    #[serde(default)]
+    granularity: StatsGranularity,
+}
+
+#[get("/v1/stats")]
+pub async fn get_stats(data: web::Data<AppData>) -> Result<impl Responder, ApiError> {
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+    Ok(web::Json(ApiResponse {
+        error: "".into(),
+        payload: Stats::get_cached(&mut pool).await?,
+    }))
+}
+
+#[get("/v1/mods/{id}/stats")]
+pub async fn get_mod_stats(
+    data: web::Data<AppData>,
+    path: web::Path<String>,
+    query: web::Query<ModStatsQuery>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let (from, to) = parse_stats_range(query.from.as_deref(), query.to.as_deref())?;
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    // Downloads of versions that aren't accepted are only shown to developers of the mod and admins
+    let only_accepted = match auth.developer() {
+        Ok(dev) => !dev.admin && !Developer::has_access_to_mod(dev.id, &path, &mut pool).await?,
+        Err(_) => true,
+    };
+    if Mod::get_one(&path, only_accepted, &mut pool).await?.is_none() {
+        return Err(ApiError::NotFound(format!("Mod '{}' not found", path)));
+    }
+
+    Ok(web::Json(ApiResponse {
+        error: "".into(),
+        payload: ModDownloadStats::get_for_mod(
+            &path,
+            from,
+            to,
+            query.granularity,
+            only_accepted,
+            &mut pool,
+        )
+        .await?,
+    }))
+}
+
+#[get("/v1/me/stats")]
+pub async fn get_own_stats(
+    data: web::Data<AppData>,
+    query: web::Query<ModStatsQuery>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    let (from, to) = parse_stats_range(query.from.as_deref(), query.to.as_deref())?;
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".into(),
+        payload: DeveloperStats::get_for_dev(dev.id, from, to, query.granularity, &mut pool)
+            .await?,
+    }))
+}
+
+/**
+ * Defaults to the last 30 days
+ */
+fn parse_stats_range(
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(NaiveDate, NaiveDate), ApiError> {
+    let parse = |name: &str, value: &str| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").or(Err(ApiError::BadRequest(format!(
+            "Invalid {} date '{}', expected YYYY-MM-DD",
+            name, value
+        ))))
+    };
+    let to = match to {
+        Some(t) => parse("to", t)?,
+        None => Utc::now().date_naive(),
+    };
+    let from = match from {
+        Some(f) => parse("from", f)?,
+        None => to - Duration::days(29),
+    };
+    if from > to {
+        return Err(ApiError::BadRequest(
+            "from can't be later than to".to_string(),
+        ));
+    }
+    if (to - from).num_days() >= MAX_STATS_RANGE_DAYS {
+        return Err(ApiError::BadRequest(format!(
+            "Stats can't span more than {} days",
+            MAX_STATS_RANGE_DAYS
+        )));
+    }
+    Ok((from, to))
+}
@@ -1,21 +1,222 @@
-use actix_web::{get, web, Responder};
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use serde::Deserialize;
+use sqlx::Acquire;
 
 use crate::{
+    extractors::auth::Auth,
     types::{
         api::{ApiError, ApiResponse},
-        models::tag::Tag,
+        models::{
+            mod_entity::Mod,
+            tag::{Tag, TagDetails},
+        },
     },
     AppData,
 };
 
+#[derive(Deserialize)]
+struct TagPath {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct IndexQuery {
+    #[serde(default)]
+    detailed: bool,
+}
+
+#[derive(Deserialize)]
+struct CreateTagPayload {
+    name: String,
+    display_name: Option<String>,
+    description: Option<String>,
+    icon: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UpdateTagPayload {
+    name: Option<String>,
+    display_name: Option<String>,
+    description: Option<String>,
+    icon: Option<String>,
+    retired: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct MergeTagPayload {
+    into: String,
+}
+
+#[derive(Deserialize)]
+struct WeeklyTopQuery {
+    tags: Option<String>,
+    limit: Option<i64>,
+}
+
 #[get("/v1/tags")]
-pub async fn index(data: web::Data<AppData>) -> Result<impl Responder, ApiError> {
+pub async fn index(
+    data: web::Data<AppData>,
+    query: web::Query<IndexQuery>,
+) -> Result<HttpResponse, ApiError> {
     let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
 
+    // The plain name list is what the game fetches, so metadata is opt-in
+    if query.detailed {
+        let tags = Tag::get_detailed_tags(&mut pool).await?;
+        return Ok(HttpResponse::Ok().json(ApiResponse {
+            error: "".to_string(),
+            payload: tags,
+        }));
+    }
+
     let tags = Tag::get_tags(&mut pool).await?;
 
-    Ok(web::Json(ApiResponse {
+    Ok(HttpResponse::Ok().json(ApiResponse {
         error: "".to_string(),
         payload: tags,
     }))
 }
+
+#[post("/v1/tags")]
+pub async fn create(
+    data: web::Data<AppData>,
+    payload: web::Json<CreateTagPayload>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    if !dev.admin {
+        return Err(ApiError::Forbidden);
+    }
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    let tag = Tag::create(
+        &payload.name,
+        payload.display_name.as_deref(),
+        payload.description.as_deref(),
+        payload.icon.as_deref(),
+        &mut pool,
+    )
+    .await?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: tag,
+    }))
+}
+
+#[put("/v1/tags/{name}")]
+pub async fn update(
+    data: web::Data<AppData>,
+    path: web::Path<TagPath>,
+    payload: web::Json<UpdateTagPayload>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    if !dev.admin {
+        return Err(ApiError::Forbidden);
+    }
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    let tag = find_tag(&path.name, &mut pool).await?;
+    let tag = Tag::update(
+        &tag,
+        payload.name.as_deref(),
+        payload.display_name.as_deref(),
+        payload.description.as_deref(),
+        payload.icon.as_deref(),
+        payload.retired,
+        &mut pool,
+    )
+    .await?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: tag,
+    }))
+}
+
+#[post("/v1/tags/{name}/merge")]
+pub async fn merge(
+    data: web::Data<AppData>,
+    path: web::Path<TagPath>,
+    payload: web::Json<MergeTagPayload>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    if !dev.admin {
+        return Err(ApiError::Forbidden);
+    }
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    let from = find_tag(&path.name, &mut pool).await?;
+    let into = find_tag(&payload.into, &mut pool).await?;
+    if from.id == into.id {
+        return Err(ApiError::BadRequest(
+            "Can't merge a tag into itself".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
+    if let Err(e) = Tag::merge(from.id, into.id, &mut transaction).await {
+        transaction
+            .rollback()
+            .await
+            .or(Err(ApiError::TransactionError))?;
+        return Err(e);
+    }
+    transaction
+        .commit()
+        .await
+        .or(Err(ApiError::TransactionError))?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+/**
+ * Retired tags stay on the mods that have them, but aren't listed or accepted on upload anymore
+ */
+#[delete("/v1/tags/{name}")]
+pub async fn retire(
+    data: web::Data<AppData>,
+    path: web::Path<TagPath>,
+    auth: Auth,
+) -> Result<impl Responder, ApiError> {
+    let dev = auth.developer()?;
+    if !dev.admin {
+        return Err(ApiError::Forbidden);
+    }
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    let tag = find_tag(&path.name, &mut pool).await?;
+    Tag::update(&tag, None, None, None, None, Some(true), &mut pool).await?;
+
+    Ok(HttpResponse::NoContent())
+}
+
+#[get("/v1/tags/weekly-top")]
+pub async fn weekly_top(
+    data: web::Data<AppData>,
+    query: web::Query<WeeklyTopQuery>,
+) -> Result<impl Responder, ApiError> {
+    let mut pool = data.db.acquire().await.or(Err(ApiError::DbAcquireError))?;
+
+    let tags = match &query.tags {
+        Some(t) => Tag::parse_tags(t, &mut pool).await?,
+        None => vec![],
+    };
+    let limit = query.limit.unwrap_or(10).clamp(1, 50);
+
+    let top = Mod::get_weekly_top(&tags, limit, &mut pool).await?;
+
+    Ok(web::Json(ApiResponse {
+        error: "".to_string(),
+        payload: top,
+    }))
+}
+
+async fn find_tag(name: &str, pool: &mut sqlx::PgConnection) -> Result<TagDetails, ApiError> {
+    match Tag::get_by_name(name, pool).await? {
+        None => Err(ApiError::NotFound(format!("Tag '{}' not found", name))),
+        Some(t) => Ok(t),
+    }
+}
@@ -1,14 +1,22 @@
 use sqlx::PgConnection;
 
-pub async fn start(pool: &mut PgConnection) -> Result<(), String> {
+pub async fn start(pool: &mut PgConnection, dedup_window_hours: i32) -> Result<(), String> {
     // update mod_versions counts
+    // flushed downloads are counted and move the refresh time forward themselves, so it never goes back here
     if let Err(e) = sqlx::query!(
         "UPDATE mod_versions mv SET download_count = mv.download_count + (
-            SELECT COUNT(DISTINCT md.ip) FROM mod_downloads md
-            WHERE md.mod_version_id = mv.id AND md.time_downloaded > mv.last_download_cache_refresh 
-        ), last_download_cache_refresh = now()
+            SELECT COUNT(*) FROM mod_downloads md
+            WHERE md.mod_version_id = mv.id AND md.time_downloaded > mv.last_download_cache_refresh
+            AND NOT EXISTS (
+                SELECT 1 FROM mod_downloads prev
+                WHERE prev.mod_version_id = md.mod_version_id AND prev.ip_hash = md.ip_hash
+                AND prev.id < md.id
+                AND prev.time_downloaded > md.time_downloaded - make_interval(hours => $1)
+            )
+        ), last_download_cache_refresh = GREATEST(mv.last_download_cache_refresh, now())
         FROM mod_version_statuses mvs
-        WHERE mv.status_id = mvs.id AND mvs.status = 'accepted'"
+        WHERE mv.status_id = mvs.id AND mvs.status = 'accepted'",
+        dedup_window_hours
     )
     .execute(&mut *pool)
     .await
@@ -18,17 +26,27 @@ pub async fn start(pool: &mut PgConnection) -> Result<(), String> {
     }
 
     if let Err(e) = sqlx::query!(
-        "UPDATE mods m SET download_count = (
-            SELECT COUNT(DISTINCT md.ip) FROM mod_downloads md
+        "UPDATE mods m SET download_count = m.download_count + (
+            SELECT COUNT(*) FROM mod_downloads md
             INNER JOIN mod_versions mv ON md.mod_version_id = mv.id
             INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
             WHERE mv.mod_id = m.id AND mvs.status = 'accepted'
-        ), last_download_cache_refresh = now()
+            AND md.time_downloaded > m.last_download_cache_refresh
+            AND NOT EXISTS (
+                SELECT 1 FROM mod_downloads prev
+                INNER JOIN mod_versions pmv ON prev.mod_version_id = pmv.id
+                INNER JOIN mod_version_statuses pmvs ON pmvs.mod_version_id = pmv.id
+                WHERE pmv.mod_id = m.id AND pmvs.status = 'accepted' AND prev.ip_hash = md.ip_hash
+                AND prev.id < md.id
+                AND prev.time_downloaded > md.time_downloaded - make_interval(hours => $1)
+            )
+        ), last_download_cache_refresh = GREATEST(m.last_download_cache_refresh, now())
         WHERE m.id IN (
             SELECT DISTINCT mv.mod_id FROM mod_versions mv 
             INNER JOIN mod_version_statuses mvs ON mv.status_id = mvs.id
             WHERE mvs.status = 'accepted'
-        )"
+        )",
+        dedup_window_hours
     )
     .execute(&mut *pool)
     .await
@@ -0,0 +1,65 @@
+use sqlx::PgConnection;
+
+pub async fn start(
+    pool: &mut PgConnection,
+    retention_days: i32,
+    salt_rotation_days: i32,
+    dedup_window_hours: i32,
+) -> Result<(), String> {
+    if let Err(e) = sqlx::query!(
+        "INSERT INTO download_salts (salt)
+        SELECT sha256(convert_to(gen_random_uuid()::text || gen_random_uuid()::text, 'UTF8'))
+        WHERE NOT EXISTS (
+            SELECT 1 FROM download_salts
+            WHERE created_at > now() - make_interval(days => $1)
+        )",
+        salt_rotation_days
+    )
+    .execute(&mut *pool)
+    .await
+    {
+        log::error!("{}", e);
+        return Err("Error rotating download salt".to_string());
+    }
+
+    // Without the old salts, old hashes can't be matched to an address anymore.
+    // A replaced salt is kept until the dedup window has passed, downloads are still checked against it
+    if let Err(e) = sqlx::query!(
+        "DELETE FROM download_salts ds WHERE EXISTS (
+            SELECT 1 FROM download_salts newer
+            WHERE (newer.created_at, newer.id) > (ds.created_at, ds.id)
+            AND newer.created_at < now() - make_interval(hours => $1)
+        )",
+        dedup_window_hours
+    )
+    .execute(&mut *pool)
+    .await
+    {
+        log::error!("{}", e);
+        return Err("Error removing old download salts".to_string());
+    }
+
+    // Downloads that haven't been added to the cached counts yet keep their hash, so they still count
+    if let Err(e) = sqlx::query!(
+        "UPDATE mod_downloads md SET ip_hash = NULL
+        FROM mod_versions mv
+        INNER JOIN mods m ON m.id = mv.mod_id
+        INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+        WHERE md.mod_version_id = mv.id
+        AND md.ip_hash IS NOT NULL
+        AND md.time_downloaded < now() - make_interval(days => $1)
+        AND (mvs.status != 'accepted' OR (
+            md.time_downloaded <= mv.last_download_cache_refresh
+            AND md.time_downloaded <= m.last_download_cache_refresh
+        ))",
+        retention_days
+    )
+    .execute(&mut *pool)
+    .await
+    {
+        log::error!("{}", e);
+        return Err("Error removing old download hashes".to_string());
+    }
+
+    Ok(())
+}
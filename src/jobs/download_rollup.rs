@@ -0,0 +1,45 @@
+use sqlx::PgConnection;
+
+pub async fn start(pool: &mut PgConnection) -> Result<(), String> {
+    // The last rolled up day is recounted too, it was probably still going on during the previous run
+    if let Err(e) = sqlx::query!(
+        "INSERT INTO mod_download_rollups (mod_version_id, day, download_count)
+        SELECT md.mod_version_id, (md.time_downloaded AT TIME ZONE 'utc')::date, COUNT(*)
+        FROM mod_downloads md
+        WHERE md.time_downloaded >= COALESCE(
+            (SELECT MAX(day) FROM mod_download_rollups)::timestamp AT TIME ZONE 'utc',
+            '-infinity'
+        )
+        GROUP BY 1, 2
+        ON CONFLICT (mod_version_id, day) DO UPDATE SET download_count = excluded.download_count"
+    )
+    .execute(&mut *pool)
+    .await
+    {
+        log::error!("{}", e);
+        return Err("Error rolling up mod downloads".to_string());
+    }
+
+    if let Err(e) = sqlx::query!(
+        "INSERT INTO mod_download_dimension_rollups (mod_version_id, day, dimension, value, download_count)
+        SELECT md.mod_version_id, (md.time_downloaded AT TIME ZONE 'utc')::date, d.dimension, COALESCE(d.value, 'unknown'), COUNT(*)
+        FROM mod_downloads md
+        CROSS JOIN LATERAL (
+            VALUES ('platform'::download_dimension, md.platform), ('gd', md.gd), ('geode', md.geode)
+        ) d(dimension, value)
+        WHERE md.time_downloaded >= COALESCE(
+            (SELECT MAX(day) FROM mod_download_dimension_rollups)::timestamp AT TIME ZONE 'utc',
+            '-infinity'
+        )
+        GROUP BY 1, 2, 3, 4
+        ON CONFLICT (mod_version_id, day, dimension, value) DO UPDATE SET download_count = excluded.download_count"
+    )
+    .execute(&mut *pool)
+    .await
+    {
+        log::error!("{}", e);
+        return Err("Error rolling up mod download details".to_string());
+    }
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
 use crate::AppData;
 
 mod download_cache;
+mod download_retention;
+mod download_rollup;
+mod trending;
 
 pub async fn start_job(name: &str, app_data: AppData) -> Result<(), String> {
     match name {
@@ -10,7 +13,37 @@ pub async fn start_job(name: &str, app_data: AppData) -> Result<(), String> {
                 .acquire()
                 .await
                 .or(Err("Couldn't connect to database"))?;
-            download_cache::start(&mut pool).await
+            download_cache::start(&mut pool, app_data.download_dedup_window_hours).await
+        }
+        "download-rollup" => {
+            let mut pool = app_data
+                .db
+                .acquire()
+                .await
+                .or(Err("Couldn't connect to database"))?;
+            download_rollup::start(&mut pool).await
+        }
+        "download-retention" => {
+            let mut pool = app_data
+                .db
+                .acquire()
+                .await
+                .or(Err("Couldn't connect to database"))?;
+            download_retention::start(
+                &mut pool,
+                app_data.download_retention_days,
+                app_data.download_salt_rotation_days,
+                app_data.download_dedup_window_hours,
+            )
+            .await
+        }
+        "trending" => {
+            let mut pool = app_data
+                .db
+                .acquire()
+                .await
+                .or(Err("Couldn't connect to database"))?;
+            trending::start(&mut pool).await
         }
         _ => Err(format!("Job not found {}", name)),
     }
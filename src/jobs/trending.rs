@@ -0,0 +1,37 @@
+use sqlx::PgConnection;
+
+/// Only downloads from this many days back count towards trending
+const TRENDING_WINDOW_DAYS: i32 = 14;
+/// A download loses half of its weight every this many hours
+const TRENDING_HALF_LIFE_HOURS: f64 = 72.0;
+
+pub async fn start(pool: &mut PgConnection) -> Result<(), String> {
+    if let Err(e) = sqlx::query!(
+        "UPDATE mods m SET
+            trending_score = COALESCE(t.score, 0),
+            weekly_download_count = COALESCE(t.weekly, 0)
+        FROM mods m2
+        LEFT JOIN (
+            SELECT mv.mod_id,
+                SUM(power(0.5, EXTRACT(EPOCH FROM now() - md.time_downloaded)::float8 / 3600 / $2::float8)) AS score,
+                COUNT(*) FILTER (WHERE md.time_downloaded > now() - interval '7 days') AS weekly
+            FROM mod_downloads md
+            INNER JOIN mod_versions mv ON mv.id = md.mod_version_id
+            INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+            WHERE mvs.status = 'accepted'
+            AND md.time_downloaded > now() - make_interval(days => $1)
+            GROUP BY mv.mod_id
+        ) t ON t.mod_id = m2.id
+        WHERE m.id = m2.id",
+        TRENDING_WINDOW_DAYS,
+        TRENDING_HALF_LIFE_HOURS
+    )
+    .execute(&mut *pool)
+    .await
+    {
+        log::error!("{}", e);
+        return Err("Error updating mod trending scores".to_string());
+    }
+
+    Ok(())
+}
@@ -9,9 +9,11 @@ use actix_web::{
 use clap::Parser;
 use env_logger::Env;
 use log::info;
+use std::{sync::Arc, time::Duration};
 
 use crate::types::api;
 use crate::types::api::ApiError;
+use crate::types::models::download::DownloadBuffer;
 
 mod auth;
 mod endpoints;
@@ -25,6 +27,10 @@ pub struct AppData {
     app_url: String,
     github_client_id: String,
     github_client_secret: String,
+    download_retention_days: i32,
+    download_salt_rotation_days: i32,
+    download_dedup_window_hours: i32,
+    downloads: Arc<DownloadBuffer>,
 }
 
 #[derive(Debug, Parser)]
@@ -46,6 +52,18 @@ async fn openapifile() -> Result<impl Responder, ApiError> {
     Ok(file)
 }
 
+/**
+ * Reads a setting from the environment, falling back to default when it isn't set
+ */
+fn parse_env<T: std::str::FromStr>(name: &str, default: T) -> anyhow::Result<T> {
+    match dotenvy::var(name) {
+        Ok(x) => x
+            .parse::<T>()
+            .map_err(|_| anyhow::anyhow!("Invalid value '{}' for {}", x, name)),
+        Err(_) => Ok(default),
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     env_logger::init_from_env(Env::default().default_filter_or("info"));
@@ -70,12 +88,32 @@ async fn main() -> anyhow::Result<()> {
     let app_url = dotenvy::var("APP_URL").unwrap_or("http://localhost".to_string());
     let github_client = dotenvy::var("GITHUB_CLIENT_ID").unwrap_or("".to_string());
     let github_secret = dotenvy::var("GITHUB_CLIENT_SECRET").unwrap_or("".to_string());
+    let download_retention_days = parse_env("DOWNLOAD_RETENTION_DAYS", 30)?;
+    let download_salt_rotation_days = parse_env("DOWNLOAD_SALT_ROTATION_DAYS", 30)?;
+    if download_retention_days < 1 {
+        anyhow::bail!("DOWNLOAD_RETENTION_DAYS has to be at least 1");
+    }
+    if download_salt_rotation_days < 1 {
+        anyhow::bail!("DOWNLOAD_SALT_ROTATION_DAYS has to be at least 1");
+    }
+    let download_dedup_window_hours = parse_env("DOWNLOAD_DEDUP_WINDOW_HOURS", 24)?;
+    if download_dedup_window_hours < 1 {
+        anyhow::bail!("DOWNLOAD_DEDUP_WINDOW_HOURS has to be at least 1");
+    }
+    let download_flush_seconds: u64 = parse_env("DOWNLOAD_FLUSH_INTERVAL_SECONDS", 10)?;
+    if download_flush_seconds == 0 {
+        anyhow::bail!("DOWNLOAD_FLUSH_INTERVAL_SECONDS has to be at least 1");
+    }
 
     let app_data = AppData {
         db: pool.clone(),
         app_url: app_url.clone(),
         github_client_id: github_client.clone(),
         github_client_secret: github_secret.clone(),
+        download_retention_days,
+        download_salt_rotation_days,
+        download_dedup_window_hours,
+        downloads: Arc::new(DownloadBuffer::new(download_dedup_window_hours)),
     };
 
     let args = Args::parse();
@@ -87,6 +125,17 @@ async fn main() -> anyhow::Result<()> {
         return anyhow::Ok(());
     }
 
+    let downloads = app_data.downloads.clone();
+    let flush_pool = pool.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(download_flush_seconds));
+        loop {
+            interval.tick().await;
+            downloads.flush(&flush_pool).await;
+        }
+    });
+    let downloads = app_data.downloads.clone();
+
     info!("Starting server on {}:{}", addr, port);
     let server = HttpServer::new(move || {
         App::new()
@@ -103,15 +152,23 @@ async fn main() -> anyhow::Result<()> {
             .wrap(Logger::default())
             .service(endpoints::mods::index)
             .service(endpoints::mods::get_mod_updates)
+            .service(endpoints::mods::suggest)
+            .service(endpoints::mods::get_batch)
             .service(endpoints::mods::get)
             .service(endpoints::mods::create)
             .service(endpoints::mods::update_mod)
             .service(endpoints::mods::get_logo)
+            .service(endpoints::mods::deprecate_mod)
+            .service(endpoints::mods::undeprecate_mod)
             .service(endpoints::mod_versions::get_version_index)
             .service(endpoints::mod_versions::get_one)
+            .service(endpoints::mod_versions::get_diff)
             .service(endpoints::mod_versions::download_version)
             .service(endpoints::mod_versions::create_version)
             .service(endpoints::mod_versions::update_version)
+            .service(endpoints::mod_versions::replace_version)
+            .service(endpoints::mod_versions::withdraw_version)
+            .service(endpoints::mod_versions::get_status_history)
             .service(endpoints::auth::github::poll_github_login)
             .service(endpoints::auth::github::start_github_login)
             .service(endpoints::developers::developer_index)
@@ -125,7 +182,25 @@ async fn main() -> anyhow::Result<()> {
             .service(endpoints::developers::get_me)
             .service(endpoints::developers::update_developer)
             .service(endpoints::tags::index)
+            .service(endpoints::tags::weekly_top)
+            .service(endpoints::tags::create)
+            .service(endpoints::tags::update)
+            .service(endpoints::tags::merge)
+            .service(endpoints::tags::retire)
+            .service(endpoints::collections::get_own)
+            .service(endpoints::collections::get_one)
+            .service(endpoints::collections::resolve)
+            .service(endpoints::collections::create)
+            .service(endpoints::collections::update)
+            .service(endpoints::collections::delete)
+            .service(endpoints::lockfiles::create)
+            .service(endpoints::lockfiles::verify)
+            .service(endpoints::gd_versions::index)
+            .service(endpoints::gd_versions::create)
+            .service(endpoints::gd_versions::update)
             .service(endpoints::stats::get_stats)
+            .service(endpoints::stats::get_mod_stats)
+            .service(endpoints::stats::get_own_stats)
             .service(openapifile)
             .service(fs::Files::new("/static", ".").show_files_listing())
             .service(health)
@@ -139,5 +214,8 @@ async fn main() -> anyhow::Result<()> {
         server.run().await?;
     }
 
+    // Whatever is still buffered would be lost on shutdown otherwise
+    downloads.flush(&pool).await;
+
     anyhow::Ok(())
 }
@@ -1,12 +1,18 @@
 use actix_web::{error::QueryPayloadError, http::header::ContentType, HttpRequest, HttpResponse};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use reqwest::StatusCode;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::fmt::Display;
 
+use super::models::mod_gd_version::VerPlatform;
+
 #[derive(Serialize, Deserialize)]
 pub struct PaginatedData<T> {
     pub data: Vec<T>,
     pub count: i64,
+    /// Set when the page is full, pass it back as `cursor` to get the next one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -72,9 +78,55 @@ pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> actix_
     ApiError::BadRequest(err.to_string()).into()
 }
 
+/**
+ * Cursors are opaque to clients, they're base64 encoded json of the last item's sort key
+ */
+pub fn encode_cursor<T: Serialize>(cursor: &T) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).unwrap_or_default())
+}
+
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> Result<T, ApiError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|x| serde_json::from_slice(&x).ok())
+        .ok_or(ApiError::BadRequest("Invalid cursor".to_string()))
+}
+
+#[derive(Serialize)]
+pub struct UploadWarnings {
+    pub warnings: Vec<String>,
+}
+
+/**
+ * Uploads without warnings keep responding with No Content
+ */
+pub fn upload_response(warnings: Vec<String>) -> HttpResponse {
+    if warnings.is_empty() {
+        return HttpResponse::NoContent().finish();
+    }
+    HttpResponse::Ok().json(ApiResponse {
+        error: "".to_string(),
+        payload: UploadWarnings { warnings },
+    })
+}
+
 pub fn create_download_link(app_url: &str, mod_id: &str, version: &str) -> String {
     format!(
         "{}/v1/mods/{}/versions/{}/download",
         app_url, mod_id, version
     )
 }
+
+pub fn create_platform_download_link(
+    app_url: &str,
+    mod_id: &str,
+    version: &str,
+    platform: VerPlatform,
+) -> String {
+    format!(
+        "{}?platform={}",
+        create_download_link(app_url, mod_id, version),
+        platform.as_str()
+    )
+}
@@ -0,0 +1,29 @@
+use semver::Version;
+use sqlx::{Postgres, QueryBuilder};
+
+// Which mod versions a Geode loader can run is decided by the geode_compatible SQL function:
+// - alpha loaders only get mods made for that exact alpha
+// - everything else needs the same major version, never gets alpha mods,
+//   and only gets mods that don't require a newer minor / patch / beta than the loader
+
+/**
+ * Parses a loader version sent by a client, the "v" prefix is optional
+ */
+pub fn parse_loader_version(version: &str) -> Option<Version> {
+    Version::parse(version.trim_start_matches('v')).ok()
+}
+
+/**
+ * Pushes " AND geode_compatible(column, loader)" to the builder
+ */
+pub fn push_compatible_filter(
+    builder: &mut QueryBuilder<'_, Postgres>,
+    column: &str,
+    loader: &Version,
+) {
+    builder.push(" AND geode_compatible(");
+    builder.push(column);
+    builder.push(", ");
+    builder.push_bind(loader.to_string());
+    builder.push(")");
+}
@@ -1,3 +1,4 @@
 pub mod api;
+pub mod geode_compat;
 pub mod mod_json;
 pub mod models;
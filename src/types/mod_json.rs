@@ -16,7 +16,10 @@ use super::{
     models::{
         dependency::{DependencyCreate, DependencyImportance, ModVersionCompare},
         incompatibility::{IncompatibilityCreate, IncompatibilityImportance},
-        mod_gd_version::DetailedGDVersion,
+        mod_entity::download_geode_file,
+        mod_gd_version::{DetailedGDVersion, VerPlatform},
+        mod_version::ModVersionChannel,
+        mod_version_artifact::{ModVersionArtifactCreate, ModVersionArtifactUpload},
     },
 };
 
@@ -49,9 +52,12 @@ pub struct ModJson {
     pub download_url: String,
     #[serde(default, skip_deserializing)]
     pub hash: String,
+    #[serde(default, skip_deserializing)]
+    pub artifacts: Vec<ModVersionArtifactCreate>,
     #[serde(default, rename = "early-load")]
     pub early_load: bool,
     pub api: Option<serde_json::Value>,
+    pub channel: Option<ModVersionChannel>,
     pub gd: DetailedGDVersion,
     #[serde(skip_deserializing, skip_serializing)]
     pub logo: Vec<u8>,
@@ -200,6 +206,86 @@ impl ModJson {
         Ok(json)
     }
 
+    /**
+     * Downloads the per-platform packages of this version. Each one has to be the same mod version
+     * and contain the binary for its platform.
+     */
+    pub async fn add_artifacts(
+        &mut self,
+        uploads: &[ModVersionArtifactUpload],
+    ) -> Result<(), ApiError> {
+        for upload in uploads {
+            if upload.platform == VerPlatform::Android || upload.platform == VerPlatform::Mac {
+                return Err(ApiError::BadRequest("Invalid artifact platform. Use android32 / android64 for android and mac-intel / mac-arm for mac".to_string()));
+            }
+            if self.artifacts.iter().any(|x| x.platform == upload.platform) {
+                return Err(ApiError::BadRequest(format!(
+                    "Duplicated artifact for platform {}",
+                    upload.platform.as_str()
+                )));
+            }
+
+            let mut file = download_geode_file(&upload.download_link).await?;
+            let artifact = ModJson::from_zip(&mut file, &upload.download_link, false)?;
+            if artifact.id != self.id || artifact.version != self.version {
+                return Err(ApiError::BadRequest(format!(
+                    "Artifact for platform {} is {} {}, expected {} {}",
+                    upload.platform.as_str(),
+                    artifact.id,
+                    artifact.version,
+                    self.id,
+                    self.version
+                )));
+            }
+            if !artifact.has_platform(upload.platform) {
+                return Err(ApiError::BadRequest(format!(
+                    "Artifact for platform {} doesn't contain a binary for it",
+                    upload.platform.as_str()
+                )));
+            }
+
+            self.set_platform(upload.platform);
+            self.artifacts.push(ModVersionArtifactCreate {
+                platform: upload.platform,
+                download_link: artifact.download_url,
+                hash: artifact.hash,
+            });
+        }
+        Ok(())
+    }
+
+    fn has_platform(&self, platform: VerPlatform) -> bool {
+        match platform {
+            VerPlatform::Android => self.android32 || self.android64,
+            VerPlatform::Android32 => self.android32,
+            VerPlatform::Android64 => self.android64,
+            VerPlatform::Ios => self.ios,
+            VerPlatform::Mac => self.mac_arm || self.mac_intel,
+            VerPlatform::MacArm => self.mac_arm,
+            VerPlatform::MacIntel => self.mac_intel,
+            VerPlatform::Win => self.windows,
+        }
+    }
+
+    fn set_platform(&mut self, platform: VerPlatform) {
+        match platform {
+            VerPlatform::Android => {
+                self.android32 = true;
+                self.android64 = true;
+            }
+            VerPlatform::Android32 => self.android32 = true,
+            VerPlatform::Android64 => self.android64 = true,
+            VerPlatform::Ios => self.ios = true,
+            VerPlatform::Mac => {
+                self.mac_arm = true;
+                self.mac_intel = true;
+            }
+            VerPlatform::MacArm => self.mac_arm = true,
+            VerPlatform::MacIntel => self.mac_intel = true,
+            VerPlatform::Win => self.windows = true,
+        }
+    }
+
     fn check_mac_binary(file: &mut ZipFile) -> Result<(bool, bool), ApiError> {
         // 12 bytes is all we need
         let mut bytes: Vec<u8> = vec![0; 12];
@@ -0,0 +1,452 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::SecondsFormat;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    PgConnection,
+};
+
+use crate::types::{api::ApiError, mod_json::split_version_and_compare};
+
+use super::{
+    dependency::ModVersionCompare,
+    developer::DeveloperProfile,
+    mod_gd_version::VerPlatform,
+    mod_version::{ModVersion, ModVersionChannel},
+};
+
+pub const MAX_COLLECTION_MODS: usize = 250;
+
+#[derive(Serialize, Clone)]
+pub struct Collection {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub developer: DeveloperProfile,
+    pub mods: Vec<CollectionEntry>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CollectionEntry {
+    pub mod_id: String,
+    pub version: Option<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CollectionEntryPayload {
+    pub mod_id: String,
+    pub version: Option<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ResolvedCollectionEntry {
+    pub mod_id: String,
+    pub note: Option<String>,
+    pub version: ModVersion,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ResolvedCollection {
+    pub id: i32,
+    pub name: String,
+    pub mods: Vec<ResolvedCollectionEntry>,
+    /// Mods that don't have a version matching the requirement for this platform, gd and loader
+    pub unresolved: Vec<String>,
+}
+
+struct CollectionRecord {
+    id: i32,
+    name: String,
+    description: Option<String>,
+    developer_id: i32,
+    username: String,
+    display_name: String,
+    verified: bool,
+    admin: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+struct CollectionEntryRecord {
+    collection_id: i32,
+    mod_id: String,
+    version: Option<String>,
+    compare: Option<ModVersionCompare>,
+    note: Option<String>,
+}
+
+impl CollectionRecord {
+    fn into_collection(self, mods: Vec<CollectionEntry>) -> Collection {
+        Collection {
+            id: self.id,
+            name: self.name,
+            description: self.description,
+            developer: DeveloperProfile {
+                id: self.developer_id,
+                username: self.username,
+                display_name: self.display_name,
+                verified: self.verified,
+                admin: self.admin,
+            },
+            mods,
+            created_at: self.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            updated_at: self.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+impl CollectionEntryRecord {
+    fn into_entry(self) -> CollectionEntry {
+        CollectionEntry {
+            mod_id: self.mod_id,
+            version: match (self.compare, self.version) {
+                (Some(compare), Some(version)) => Some(format!("{}{}", compare, version)),
+                _ => None,
+            },
+            note: self.note,
+        }
+    }
+}
+
+impl Collection {
+    pub async fn get_one(id: i32, pool: &mut PgConnection) -> Result<Option<Collection>, ApiError> {
+        let record = match sqlx::query_as!(
+            CollectionRecord,
+            "SELECT c.id, c.name, c.description, c.developer_id, d.username, d.display_name,
+                d.verified, d.admin, c.created_at, c.updated_at
+            FROM collections c
+            INNER JOIN developers d ON d.id = c.developer_id
+            WHERE c.id = $1",
+            id
+        )
+        .fetch_optional(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(None) => return Ok(None),
+            Ok(Some(r)) => r,
+        };
+
+        let mut entries = Collection::get_entries(&vec![record.id], pool).await?;
+        let mods = entries.remove(&record.id).unwrap_or_default();
+        Ok(Some(record.into_collection(mods)))
+    }
+
+    pub async fn get_for_developer(
+        developer_id: i32,
+        pool: &mut PgConnection,
+    ) -> Result<Vec<Collection>, ApiError> {
+        let records = match sqlx::query_as!(
+            CollectionRecord,
+            "SELECT c.id, c.name, c.description, c.developer_id, d.username, d.display_name,
+                d.verified, d.admin, c.created_at, c.updated_at
+            FROM collections c
+            INNER JOIN developers d ON d.id = c.developer_id
+            WHERE c.developer_id = $1
+            ORDER BY c.updated_at DESC, c.id DESC",
+            developer_id
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+
+        let ids: Vec<i32> = records.iter().map(|x| x.id).collect();
+        let mut entries = Collection::get_entries(&ids, pool).await?;
+        Ok(records
+            .into_iter()
+            .map(|x| {
+                let mods = entries.remove(&x.id).unwrap_or_default();
+                x.into_collection(mods)
+            })
+            .collect())
+    }
+
+    /**
+     * Should be called inside a transaction, since it touches multiple tables
+     */
+    pub async fn create(
+        developer_id: i32,
+        name: &str,
+        description: Option<&str>,
+        mods: &[CollectionEntryPayload],
+        pool: &mut PgConnection,
+    ) -> Result<Collection, ApiError> {
+        validate_name(name)?;
+        validate_description(description)?;
+
+        let id = match sqlx::query_scalar!(
+            "INSERT INTO collections (developer_id, name, description)
+            VALUES ($1, $2, $3)
+            RETURNING id",
+            developer_id,
+            name.trim(),
+            description
+        )
+        .fetch_one(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+
+        Collection::set_entries(id, mods, pool).await?;
+
+        match Collection::get_one(id, pool).await? {
+            None => Err(ApiError::InternalError),
+            Some(c) => Ok(c),
+        }
+    }
+
+    /**
+     * Should be called inside a transaction, since it touches multiple tables.
+     * The mod list is replaced as a whole when given.
+     */
+    pub async fn update(
+        id: i32,
+        name: Option<&str>,
+        description: Option<&str>,
+        mods: Option<&[CollectionEntryPayload]>,
+        pool: &mut PgConnection,
+    ) -> Result<Collection, ApiError> {
+        if let Some(name) = name {
+            validate_name(name)?;
+        }
+        validate_description(description)?;
+
+        if let Err(e) = sqlx::query!(
+            "UPDATE collections
+            SET name = COALESCE($2, name),
+                description = COALESCE($3, description),
+                updated_at = now()
+            WHERE id = $1",
+            id,
+            name.map(|x| x.trim()),
+            description
+        )
+        .execute(&mut *pool)
+        .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+
+        if let Some(mods) = mods {
+            Collection::set_entries(id, mods, pool).await?;
+        }
+
+        match Collection::get_one(id, pool).await? {
+            None => Err(ApiError::NotFound(format!("Collection {} not found", id))),
+            Some(c) => Ok(c),
+        }
+    }
+
+    pub async fn delete(id: i32, pool: &mut PgConnection) -> Result<(), ApiError> {
+        if let Err(e) = sqlx::query!("DELETE FROM collections WHERE id = $1", id)
+            .execute(&mut *pool)
+            .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+        Ok(())
+    }
+
+    /**
+     * Expands the collection into the newest version of each mod on the channel that can be installed
+     * on the given platform, gd and loader. Download links still need to be filled in.
+     */
+    pub async fn resolve(
+        &self,
+        platform: VerPlatform,
+        gd: &str,
+        geode: &Version,
+        channel: ModVersionChannel,
+        pool: &mut PgConnection,
+    ) -> Result<ResolvedCollection, ApiError> {
+        let mut versions =
+            ModVersion::get_for_collection(self.id, platform, gd, geode, channel, pool).await?;
+
+        let mut mods = vec![];
+        let mut unresolved = vec![];
+        for entry in &self.mods {
+            match versions.remove(&entry.mod_id) {
+                None => unresolved.push(entry.mod_id.clone()),
+                Some(version) => mods.push(ResolvedCollectionEntry {
+                    mod_id: entry.mod_id.clone(),
+                    note: entry.note.clone(),
+                    version,
+                }),
+            }
+        }
+
+        Ok(ResolvedCollection {
+            id: self.id,
+            name: self.name.clone(),
+            mods,
+            unresolved,
+        })
+    }
+
+    async fn get_entries(
+        ids: &Vec<i32>,
+        pool: &mut PgConnection,
+    ) -> Result<HashMap<i32, Vec<CollectionEntry>>, ApiError> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let records = match sqlx::query_as!(
+            CollectionEntryRecord,
+            r#"SELECT collection_id, mod_id, version, compare as "compare: _", note
+            FROM collection_mods
+            WHERE collection_id = ANY($1)
+            ORDER BY collection_id, position"#,
+            ids
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+
+        let mut ret: HashMap<i32, Vec<CollectionEntry>> = HashMap::new();
+        for i in records {
+            ret.entry(i.collection_id).or_default().push(i.into_entry());
+        }
+        Ok(ret)
+    }
+
+    async fn set_entries(
+        id: i32,
+        mods: &[CollectionEntryPayload],
+        pool: &mut PgConnection,
+    ) -> Result<(), ApiError> {
+        if mods.len() > MAX_COLLECTION_MODS {
+            return Err(ApiError::BadRequest(format!(
+                "Collections can have at most {} mods",
+                MAX_COLLECTION_MODS
+            )));
+        }
+
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut mod_ids: Vec<String> = vec![];
+        let mut versions: Vec<Option<String>> = vec![];
+        let mut compares: Vec<Option<ModVersionCompare>> = vec![];
+        let mut notes: Vec<Option<String>> = vec![];
+        for i in mods {
+            if !seen.insert(&i.mod_id) {
+                return Err(ApiError::BadRequest(format!(
+                    "Mod {} is in the collection more than once",
+                    i.mod_id
+                )));
+            }
+            let requirement = match i.version.as_deref().map(|x| x.trim()) {
+                None | Some("") | Some("*") => None,
+                Some(v) => match split_version_and_compare(v) {
+                    Err(_) => {
+                        return Err(ApiError::BadRequest(format!(
+                            "Invalid version requirement {} for mod {}",
+                            v, i.mod_id
+                        )))
+                    }
+                    Ok(r) => Some(r),
+                },
+            };
+            if i.note.as_ref().is_some_and(|x| x.len() > 512) {
+                return Err(ApiError::BadRequest(format!(
+                    "Note for mod {} is too long (max 512 characters)",
+                    i.mod_id
+                )));
+            }
+
+            mod_ids.push(i.mod_id.clone());
+            versions.push(requirement.as_ref().map(|x| x.0.to_string()));
+            compares.push(requirement.map(|x| x.1));
+            notes.push(i.note.clone());
+        }
+
+        let existing = match sqlx::query_scalar!("SELECT id FROM mods WHERE id = ANY($1)", &mod_ids)
+            .fetch_all(&mut *pool)
+            .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+        if let Some(missing) = mod_ids.iter().find(|x| !existing.contains(x)) {
+            return Err(ApiError::NotFound(format!("Mod {} not found", missing)));
+        }
+
+        if let Err(e) = sqlx::query!("DELETE FROM collection_mods WHERE collection_id = $1", id)
+            .execute(&mut *pool)
+            .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+
+        for (position, mod_id) in mod_ids.iter().enumerate() {
+            if let Err(e) = sqlx::query!(
+                "INSERT INTO collection_mods (collection_id, mod_id, position, version, compare, note)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+                id,
+                mod_id,
+                position as i32,
+                versions[position],
+                compares[position] as Option<ModVersionCompare>,
+                notes[position]
+            )
+            .execute(&mut *pool)
+            .await
+            {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), ApiError> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > 64 {
+        return Err(ApiError::BadRequest(
+            "Collection name must be between 1 and 64 characters".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_description(description: Option<&str>) -> Result<(), ApiError> {
+    if description.is_some_and(|x| x.len() > 1024) {
+        return Err(ApiError::BadRequest(
+            "Collection description is too long (max 1024 characters)".to_string(),
+        ));
+    }
+    Ok(())
+}
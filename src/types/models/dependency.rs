@@ -5,7 +5,7 @@ use sqlx::{PgConnection, Postgres, QueryBuilder};
 
 use crate::types::api::ApiError;
 
-use super::mod_gd_version::{GDVersionEnum, VerPlatform};
+use super::{mod_gd_version::VerPlatform, mod_version::ModVersionChannel};
 
 #[derive(sqlx::FromRow, Clone)]
 pub struct Dependency {
@@ -22,7 +22,7 @@ pub struct DependencyCreate {
     pub importance: DependencyImportance,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct ResponseDependency {
     pub mod_id: String,
     pub version: String,
@@ -86,7 +86,7 @@ impl Display for ModVersionCompare {
     }
 }
 
-#[derive(sqlx::Type, Debug, Deserialize, Serialize, Clone, Copy)]
+#[derive(sqlx::Type, Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
 #[sqlx(type_name = "dependency_importance", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum DependencyImportance {
@@ -127,11 +127,49 @@ impl Dependency {
         Ok(())
     }
 
+    /**
+     * Returns the dependencies as declared in mod.json, without resolving them to versions
+     */
+    pub async fn get_declared_for_mod_versions(
+        ids: &Vec<i32>,
+        pool: &mut PgConnection,
+    ) -> Result<HashMap<i32, Vec<FetchedDependency>>, ApiError> {
+        let result = match sqlx::query_as!(
+            FetchedDependency,
+            r#"SELECT dependent_id as mod_version_id, version, dependency_id,
+                compare as "compare: _", importance as "importance: _"
+            FROM dependencies
+            WHERE dependent_id = ANY($1)
+            ORDER BY dependency_id"#,
+            ids
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Ok(d) => d,
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        };
+
+        let mut ret: HashMap<i32, Vec<FetchedDependency>> = HashMap::new();
+        for i in result {
+            ret.entry(i.mod_version_id).or_default().push(i);
+        }
+        Ok(ret)
+    }
+
+    /**
+     * Resolves the dependencies of the versions, recursively. A dependency can be on the given
+     * channel or on the channel of the version that needs it, whichever is less stable
+     */
     pub async fn get_for_mod_versions(
         ids: &Vec<i32>,
         platform: Option<VerPlatform>,
-        gd: Option<GDVersionEnum>,
+        gd: Option<&str>,
         geode: Option<&semver::Version>,
+        channel: ModVersionChannel,
         pool: &mut PgConnection,
     ) -> Result<HashMap<i32, Vec<FetchedDependency>>, ApiError> {
         // Fellow developer, I am sorry for what you're about to see :)
@@ -164,7 +202,7 @@ impl Dependency {
                         mv.id AS start_node,
                         ROW_NUMBER() OVER(
                             PARTITION BY dp.dependency_id, mv.id 
-                            ORDER BY dpcy_version.id DESC, mv.id DESC
+                            ORDER BY dpcy_version.version_key DESC NULLS LAST, dpcy_version.id DESC, mv.id DESC
                         ) rn 
                     FROM mod_versions mv
                     INNER JOIN mods m ON mv.mod_id = m.id
@@ -177,18 +215,8 @@ impl Dependency {
                     AND mv.id = ANY($1)
                     AND ($2 IS NULL OR dpcy_mgv.gd = $2 OR dpcy_mgv.gd = '*')
                     AND ($3 IS NULL OR dpcy_mgv.platform = $3)
-                    AND ($4 IS NULL OR (
-                        CASE
-                            WHEN SPLIT_PART($4, '-', 2) ILIKE 'alpha%' THEN $4 = dpcy_version.geode
-                            ELSE SPLIT_PART($4, '.', 1) = SPLIT_PART(dpcy_version.geode, '.', 1)
-                                AND SPLIT_PART(dpcy_version.geode, '-', 2) NOT LIKE 'alpha%'
-                                AND SPLIT_PART(dpcy_version.geode, '.', 2) <= SPLIT_PART($4, '.', 2)
-                                AND (
-                                    SPLIT_PART(dpcy_version.geode, '-', 2) = '' 
-                                    OR SPLIT_PART(dpcy_version.geode, '-', 2) <= SPLIT_PART($4, '-', 2)
-                                )
-                        END
-                    ))
+                    AND ($4 IS NULL OR geode_compatible(dpcy_version.geode, $4))
+                    AND dpcy_version.channel <= GREATEST($5, mv.channel)
                     AND SPLIT_PART(dpcy_version.version, '.', 1) = SPLIT_PART(dp.version, '.', 1)
                     AND CASE
                         WHEN dp.version = '*' THEN true
@@ -218,7 +246,7 @@ impl Dependency {
                         dt.start_node AS start_node,
                         ROW_NUMBER() OVER(
                             PARTITION BY dp2.dependency_id, mv2.id 
-                            ORDER BY dpcy_version2.id DESC, mv2.id DESC
+                            ORDER BY dpcy_version2.version_key DESC NULLS LAST, dpcy_version2.id DESC, mv2.id DESC
                         ) rn 
                     FROM mod_versions mv2
                     INNER JOIN mods m2 ON mv2.mod_id = m2.id
@@ -231,18 +259,8 @@ impl Dependency {
                     WHERE dpcy_status2.status = 'accepted'
                     AND ($2 IS NULL OR dpcy_mgv2.gd = $2 OR dpcy_mgv2.gd = '*')
                     AND ($3 IS NULL OR dpcy_mgv2.platform = $3)
-                    AND ($4 IS NULL OR (
-                        CASE
-                            WHEN SPLIT_PART($4, '-', 2) ILIKE 'alpha%' THEN $4 = dpcy_version2.geode
-                            ELSE SPLIT_PART($4, '.', 1) = SPLIT_PART(dpcy_version2.geode, '.', 1) 
-                                AND SPLIT_PART(dpcy_version2.geode, '-', 2) NOT LIKE 'alpha%'
-                                AND SPLIT_PART(dpcy_version2.geode, '.', 2) <= SPLIT_PART($4, '.', 2)
-                                AND (
-                                    SPLIT_PART(dpcy_version2.geode, '-', 2) = '' 
-                                    OR SPLIT_PART(dpcy_version2.geode, '-', 2) <= SPLIT_PART($4, '-', 2)
-                                )
-                        END
-                    ))
+                    AND ($4 IS NULL OR geode_compatible(dpcy_version2.geode, $4))
+                    AND dpcy_version2.channel <= GREATEST($5, mv2.channel)
                     AND SPLIT_PART(dpcy_version2.version, '.', 1) = SPLIT_PART(dp2.version, '.', 1)
                     AND CASE
                         WHEN dp2.version = '*' THEN true
@@ -261,7 +279,8 @@ impl Dependency {
         ).bind(ids)
         .bind(gd)
         .bind(platform)
-        .bind(geode.map(|x| x.to_string()));
+        .bind(geode.map(|x| x.to_string()))
+        .bind(channel);
 
         let result = match q.fetch_all(&mut *pool).await {
             Ok(d) => d,
@@ -135,6 +135,7 @@ impl Developer {
         Ok(PaginatedData {
             data: result,
             count,
+            next_cursor: None,
         })
     }
 
@@ -401,6 +402,18 @@ impl Developer {
             return Err(ApiError::InternalError);
         }
 
+        // display names are part of the search document of their mods
+        if let Err(e) = sqlx::query!(
+            "SELECT update_mod_search_vector(mod_id) FROM mods_developers WHERE developer_id = $1",
+            id
+        )
+        .execute(&mut *pool)
+        .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+
         Ok(())
     }
 
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, SecondsFormat};
+use serde::Serialize;
+use sqlx::PgConnection;
+
+use crate::types::api::ApiError;
+
+use super::{
+    download_stats::{to_points, DownloadPoint, StatsGranularity},
+    mod_version_status::ModVersionStatusEnum,
+};
+
+#[derive(Serialize, Debug)]
+pub struct TagRank {
+    pub tag: String,
+    pub rank: i64,
+    pub out_of: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DeveloperModStats {
+    pub id: String,
+    pub name: String,
+    pub download_count: i32,
+    pub downloads: Vec<DownloadPoint>,
+    /// Mods whose latest accepted version depends on this one
+    pub dependents: i64,
+    /// Place by total downloads among the accepted mods of each tag
+    pub tag_ranks: Vec<TagRank>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RejectedVersion {
+    pub mod_id: String,
+    pub version: String,
+    pub reason: Option<String>,
+    pub rejected_at: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DeveloperStats {
+    pub from: String,
+    pub to: String,
+    pub granularity: StatsGranularity,
+    pub download_count: i64,
+    pub downloads: Vec<DownloadPoint>,
+    pub pending_versions: i64,
+    pub rejected_versions: i64,
+    pub rejections: Vec<RejectedVersion>,
+    pub mods: Vec<DeveloperModStats>,
+}
+
+impl DeveloperStats {
+    /**
+     * Stats over every mod the developer is on, for their dashboard
+     */
+    pub async fn get_for_dev(
+        dev_id: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+        granularity: StatsGranularity,
+        pool: &mut PgConnection,
+    ) -> Result<DeveloperStats, ApiError> {
+        let mods = match sqlx::query!(
+            r#"SELECT m.id, m.download_count,
+                (SELECT mv.name FROM mod_versions mv WHERE mv.mod_id = m.id
+                ORDER BY mv.version_key DESC NULLS LAST, mv.id DESC LIMIT 1) as "name!"
+            FROM mods m
+            INNER JOIN mods_developers md ON md.mod_id = m.id
+            WHERE md.developer_id = $1
+            ORDER BY m.download_count DESC, m.id"#,
+            dev_id
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        };
+        let ids: Vec<String> = mods.iter().map(|x| x.id.clone()).collect();
+
+        let mut days: HashMap<String, Vec<(NaiveDate, i64)>> = HashMap::new();
+        let mut all_days: Vec<(NaiveDate, i64)> = vec![];
+        match sqlx::query!(
+            r#"SELECT mv.mod_id, r.day, SUM(r.download_count)::bigint as "downloads!"
+            FROM mod_download_rollups r
+            INNER JOIN mod_versions mv ON mv.id = r.mod_version_id
+            WHERE mv.mod_id = ANY($1) AND r.day >= $2 AND r.day <= $3
+            GROUP BY 1, 2"#,
+            &ids,
+            from,
+            to
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Ok(rows) => {
+                for row in rows {
+                    all_days.push((row.day, row.downloads));
+                    days.entry(row.mod_id)
+                        .or_default()
+                        .push((row.day, row.downloads));
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        };
+
+        let statuses = match sqlx::query!(
+            r#"SELECT mv.mod_id, mv.version, mvs.status as "status: ModVersionStatusEnum", mvs.info, mvs.updated_at
+            FROM mod_versions mv
+            INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+            WHERE mv.mod_id = ANY($1) AND mvs.status IN ('pending', 'rejected')
+            ORDER BY mvs.updated_at DESC, mv.id DESC"#,
+            &ids
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        };
+
+        let dependents: HashMap<String, i64> = match sqlx::query!(
+            r#"SELECT d.dependency_id, COUNT(DISTINCT mv.mod_id) as "count!"
+            FROM dependencies d
+            INNER JOIN mod_versions mv ON mv.id = d.dependent_id
+            WHERE d.dependency_id = ANY($1)
+            AND d.dependent_id = (
+                SELECT lv.id FROM mod_versions lv
+                INNER JOIN mod_version_statuses lvs ON lvs.mod_version_id = lv.id
+                WHERE lv.mod_id = mv.mod_id AND lvs.status = 'accepted'
+                ORDER BY lv.version_key DESC NULLS LAST, lv.id DESC LIMIT 1
+            )
+            GROUP BY 1"#,
+            &ids
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Ok(r) => r.into_iter().map(|x| (x.dependency_id, x.count)).collect(),
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        };
+
+        let mut tag_ranks: HashMap<String, Vec<TagRank>> = HashMap::new();
+        match sqlx::query!(
+            r#"SELECT r.mod_id as "mod_id!", r.name as "name!", r.rank as "rank!", r.out_of as "out_of!"
+            FROM (
+                SELECT mmt.mod_id, t.name,
+                    rank() OVER (PARTITION BY t.id ORDER BY m.download_count DESC) as rank,
+                    COUNT(*) OVER (PARTITION BY t.id) as out_of
+                FROM mods_mod_tags mmt
+                INNER JOIN mod_tags t ON t.id = mmt.tag_id
+                INNER JOIN mods m ON m.id = mmt.mod_id
+                WHERE t.retired_at IS NULL
+                AND EXISTS (
+                    SELECT 1 FROM mod_versions mv
+                    INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+                    WHERE mv.mod_id = m.id AND mvs.status = 'accepted'
+                )
+            ) r
+            WHERE r.mod_id = ANY($1)
+            ORDER BY r.rank, r.name"#,
+            &ids
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Ok(rows) => {
+                for row in rows {
+                    tag_ranks.entry(row.mod_id).or_default().push(TagRank {
+                        tag: row.name,
+                        rank: row.rank,
+                        out_of: row.out_of,
+                    });
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        };
+
+        let periods = granularity.periods(from, to);
+        let pending_versions = statuses
+            .iter()
+            .filter(|x| x.status == ModVersionStatusEnum::Pending)
+            .count() as i64;
+        let rejections: Vec<RejectedVersion> = statuses
+            .into_iter()
+            .filter(|x| x.status == ModVersionStatusEnum::Rejected)
+            .map(|x| RejectedVersion {
+                mod_id: x.mod_id,
+                version: x.version,
+                reason: x.info,
+                rejected_at: x.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            })
+            .collect();
+
+        Ok(DeveloperStats {
+            from: from.format("%Y-%m-%d").to_string(),
+            to: to.format("%Y-%m-%d").to_string(),
+            granularity,
+            download_count: mods.iter().map(|x| x.download_count as i64).sum(),
+            downloads: to_points(&periods, granularity, &all_days),
+            pending_versions,
+            rejected_versions: rejections.len() as i64,
+            rejections,
+            mods: mods
+                .into_iter()
+                .map(|x| DeveloperModStats {
+                    downloads: to_points(
+                        &periods,
+                        granularity,
+                        days.get(&x.id).map(|d| d.as_slice()).unwrap_or_default(),
+                    ),
+                    dependents: dependents.get(&x.id).copied().unwrap_or_default(),
+                    tag_ranks: tag_ranks.remove(&x.id).unwrap_or_default(),
+                    id: x.id,
+                    name: x.name,
+                    download_count: x.download_count,
+                })
+                .collect(),
+        })
+    }
+}
@@ -1,49 +1,157 @@
-use sqlx::{types::ipnetwork::IpNetwork, PgConnection};
+use std::{mem, sync::Mutex};
+
+use sqlx::{types::ipnetwork::IpNetwork, PgPool};
 
 use crate::types::api::ApiError;
 
-pub async fn create_download(
+use super::{mod_entity::Mod, mod_gd_version::VerPlatform, mod_version::ModVersion};
+
+/// Downloads past this many are dropped until the buffer gets flushed
+const MAX_BUFFERED_DOWNLOADS: usize = 100_000;
+
+/**
+ * What the client told us about itself when downloading
+ */
+pub struct DownloadDetails {
+    pub platform: Option<VerPlatform>,
+    pub gd: Option<String>,
+    pub geode: Option<String>,
+}
+
+struct BufferedDownload {
     ip: IpNetwork,
     mod_version_id: i32,
-    pool: &mut PgConnection,
-) -> Result<bool, ApiError> {
-    let existing = match sqlx::query!(
-        r#"
-        SELECT * FROM mod_downloads
-        WHERE ip = $1 AND mod_version_id = $2
-        "#,
-        ip,
-        mod_version_id
-    )
-    .fetch_optional(&mut *pool)
-    .await
-    {
-        Ok(e) => e,
-        Err(e) => {
-            log::error!("{}", e);
-            return Err(ApiError::InternalError);
+    details: DownloadDetails,
+}
+
+/**
+ * Downloads are kept in memory and written in bulk by flush, which runs periodically.
+ * Only a hash of the address salted with the current download salt is stored.
+ */
+pub struct DownloadBuffer {
+    pending: Mutex<Vec<BufferedDownload>>,
+    dedup_window_hours: i32,
+}
+
+impl DownloadBuffer {
+    pub fn new(dedup_window_hours: i32) -> DownloadBuffer {
+        DownloadBuffer {
+            pending: Mutex::new(vec![]),
+            dedup_window_hours,
         }
-    };
+    }
 
-    if existing.is_some() {
-        return Ok(false);
+    pub fn record(
+        &self,
+        ip: IpNetwork,
+        mod_version_id: i32,
+        mod_id: &str,
+        details: DownloadDetails,
+    ) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.len() >= MAX_BUFFERED_DOWNLOADS {
+            log::warn!("Download buffer is full, dropping download of {}", mod_id);
+            return;
+        }
+        pending.push(BufferedDownload {
+            ip,
+            mod_version_id,
+            details,
+        });
     }
 
-    match sqlx::query!(
-        r#"
-        INSERT INTO mod_downloads (ip, mod_version_id)
-        VALUES ($1, $2)
-        "#,
-        ip,
-        mod_version_id
-    )
-    .execute(&mut *pool)
-    .await
-    {
-        Ok(_) => Ok(true),
-        Err(e) => {
-            log::error!("{}", e);
-            Err(ApiError::InternalError)
+    /**
+     * Writes the buffered downloads and adds them onto the cached download counts.
+     * If that fails, the downloads are kept for the next flush.
+     */
+    pub async fn flush(&self, pool: &PgPool) {
+        let batch = mem::take(&mut *self.pending.lock().unwrap());
+        if batch.is_empty() {
+            return;
+        }
+
+        if let Err(e) = self.write(&batch, pool).await {
+            log::error!("Failed to flush {} downloads: {}", batch.len(), e);
+            let mut pending = self.pending.lock().unwrap();
+            let room = MAX_BUFFERED_DOWNLOADS.saturating_sub(pending.len());
+            pending.extend(batch.into_iter().take(room));
         }
     }
+
+    async fn write(&self, batch: &[BufferedDownload], pool: &PgPool) -> Result<(), ApiError> {
+        let mut transaction = pool.begin().await.or(Err(ApiError::TransactionError))?;
+
+        let ips: Vec<IpNetwork> = batch.iter().map(|x| x.ip).collect();
+        let version_ids: Vec<i32> = batch.iter().map(|x| x.mod_version_id).collect();
+        let platforms: Vec<Option<String>> = batch
+            .iter()
+            .map(|x| x.details.platform.map(|p| p.as_str().to_string()))
+            .collect();
+        let gds: Vec<Option<String>> = batch.iter().map(|x| x.details.gd.clone()).collect();
+        let geodes: Vec<Option<String>> = batch.iter().map(|x| x.details.geode.clone()).collect();
+        // Addresses are deduplicated against stored downloads and earlier ones in the same batch.
+        // Stored downloads can be hashed with the current or the previous salt, so both are checked.
+        // Unknown gd versions aren't kept, so clients can't fill the stats with junk
+        let inserted = match sqlx::query!(
+            r#"WITH batch AS (
+                SELECT b.n, b.ip, b.mod_version_id, sha256(ds.salt || convert_to(host(b.ip), 'UTF8')) AS ip_hash,
+                    b.platform, b.gd, b.geode
+                FROM unnest($1::inet[], $2::int[], $3::text[], $4::text[], $5::text[])
+                    WITH ORDINALITY AS b(ip, mod_version_id, platform, gd, geode, n)
+                CROSS JOIN (
+                    SELECT salt FROM download_salts
+                    ORDER BY created_at DESC, id DESC
+                    LIMIT 1
+                ) ds
+            )
+            INSERT INTO mod_downloads (mod_version_id, ip_hash, platform, gd, geode)
+            SELECT b.mod_version_id, b.ip_hash, b.platform,
+                (SELECT gv.version FROM gd_versions gv WHERE gv.version = b.gd), b.geode
+            FROM batch b
+            WHERE NOT EXISTS (
+                SELECT 1 FROM mod_downloads md
+                WHERE md.mod_version_id = b.mod_version_id
+                AND md.ip_hash IN (
+                    SELECT sha256(s.salt || convert_to(host(b.ip), 'UTF8')) FROM download_salts s
+                )
+                AND md.time_downloaded > now() - make_interval(hours => $6)
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM batch p
+                WHERE p.mod_version_id = b.mod_version_id AND p.ip_hash = b.ip_hash AND p.n < b.n
+            )
+            ORDER BY b.n
+            RETURNING id, mod_version_id"#,
+            &ips,
+            &version_ids,
+            &platforms as &[Option<String>],
+            &gds as &[Option<String>],
+            &geodes as &[Option<String>],
+            self.dedup_window_hours
+        )
+        .fetch_all(&mut *transaction)
+        .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        };
+
+        // The inserted rows are already deduplicated, so they're added onto the counts as they are
+        if !inserted.is_empty() {
+            let version_ids: Vec<i32> = inserted.iter().map(|x| x.mod_version_id).collect();
+            let download_ids: Vec<i64> = inserted.iter().map(|x| x.id).collect();
+            ModVersion::add_cached_downloads(&version_ids, &mut transaction).await?;
+            Mod::add_cached_downloads(&download_ids, self.dedup_window_hours, &mut transaction)
+                .await?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .or(Err(ApiError::TransactionError))?;
+        Ok(())
+    }
 }
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+
+use crate::types::api::ApiError;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsGranularity {
+    #[default]
+    Day,
+    Week,
+}
+
+impl StatsGranularity {
+    /**
+     * First day of the period the day falls in, weeks start on monday
+     */
+    pub fn period_start(&self, day: NaiveDate) -> NaiveDate {
+        match self {
+            StatsGranularity::Day => day,
+            StatsGranularity::Week => {
+                day - Duration::days(day.weekday().num_days_from_monday() as i64)
+            }
+        }
+    }
+
+    /**
+     * Start of every period between from and to, both included
+     */
+    pub fn periods(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let step = match self {
+            StatsGranularity::Day => Duration::days(1),
+            StatsGranularity::Week => Duration::days(7),
+        };
+        let mut ret = vec![];
+        let mut current = self.period_start(from);
+        while current <= to {
+            ret.push(current);
+            current += step;
+        }
+        ret
+    }
+}
+
+#[derive(sqlx::Type, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[sqlx(type_name = "download_dimension", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadDimension {
+    Platform,
+    Gd,
+    Geode,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DownloadPoint {
+    pub date: String,
+    pub downloads: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct VersionDownloads {
+    pub version: String,
+    pub downloads: Vec<DownloadPoint>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ModDownloadStats {
+    pub from: String,
+    pub to: String,
+    pub granularity: StatsGranularity,
+    pub total: Vec<DownloadPoint>,
+    pub versions: Vec<VersionDownloads>,
+    /// Downloads in the whole range per platform, gd version and loader version
+    pub breakdown: HashMap<DownloadDimension, HashMap<String, i64>>,
+}
+
+/**
+ * Sums daily counts into the given periods, periods without downloads are kept as 0
+ */
+pub fn to_points(
+    periods: &[NaiveDate],
+    granularity: StatsGranularity,
+    days: &[(NaiveDate, i64)],
+) -> Vec<DownloadPoint> {
+    let mut counts: HashMap<NaiveDate, i64> = HashMap::new();
+    for (day, count) in days {
+        *counts.entry(granularity.period_start(*day)).or_default() += count;
+    }
+    periods
+        .iter()
+        .map(|p| DownloadPoint {
+            date: p.format("%Y-%m-%d").to_string(),
+            downloads: counts.get(p).copied().unwrap_or_default(),
+        })
+        .collect()
+}
+
+impl ModDownloadStats {
+    /**
+     * Downloads of the mod and each of its versions, from the daily rollups
+     * only_accepted leaves out pending and rejected versions
+     */
+    pub async fn get_for_mod(
+        mod_id: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+        granularity: StatsGranularity,
+        only_accepted: bool,
+        pool: &mut PgConnection,
+    ) -> Result<ModDownloadStats, ApiError> {
+        let rows = match sqlx::query!(
+            "SELECT mv.version, r.day, r.download_count
+            FROM mod_download_rollups r
+            INNER JOIN mod_versions mv ON mv.id = r.mod_version_id
+            INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+            WHERE mv.mod_id = $1 AND r.day >= $2 AND r.day <= $3
+            AND ($4 = false OR mvs.status = 'accepted')
+            ORDER BY mv.version_key DESC NULLS LAST, mv.id DESC, r.day",
+            mod_id,
+            from,
+            to,
+            only_accepted
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        };
+
+        let mut all_days: Vec<(NaiveDate, i64)> = vec![];
+        let mut version_days: Vec<(String, Vec<(NaiveDate, i64)>)> = vec![];
+        for row in rows {
+            let count = row.download_count as i64;
+            all_days.push((row.day, count));
+            match version_days.last_mut() {
+                Some((version, days)) if *version == row.version => days.push((row.day, count)),
+                _ => version_days.push((row.version, vec![(row.day, count)])),
+            }
+        }
+
+        let breakdown_rows = match sqlx::query!(
+            r#"SELECT r.dimension as "dimension: DownloadDimension", r.value,
+                SUM(r.download_count)::bigint as "downloads!"
+            FROM mod_download_dimension_rollups r
+            INNER JOIN mod_versions mv ON mv.id = r.mod_version_id
+            INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+            WHERE mv.mod_id = $1 AND r.day >= $2 AND r.day <= $3
+            AND ($4 = false OR mvs.status = 'accepted')
+            GROUP BY 1, 2"#,
+            mod_id,
+            from,
+            to,
+            only_accepted
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        };
+        let mut breakdown: HashMap<DownloadDimension, HashMap<String, i64>> = [
+            DownloadDimension::Platform,
+            DownloadDimension::Gd,
+            DownloadDimension::Geode,
+        ]
+        .into_iter()
+        .map(|d| (d, HashMap::new()))
+        .collect();
+        for row in breakdown_rows {
+            breakdown
+                .entry(row.dimension)
+                .or_default()
+                .insert(row.value, row.downloads);
+        }
+
+        let periods = granularity.periods(from, to);
+        Ok(ModDownloadStats {
+            from: from.format("%Y-%m-%d").to_string(),
+            to: to.format("%Y-%m-%d").to_string(),
+            granularity,
+            total: to_points(&periods, granularity, &all_days),
+            versions: version_days
+                .into_iter()
+                .map(|(version, days)| VersionDownloads {
+                    version,
+                    downloads: to_points(&periods, granularity, &days),
+                })
+                .collect(),
+            breakdown,
+        })
+    }
+}
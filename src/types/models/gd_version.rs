@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+
+use chrono::SecondsFormat;
+use serde::Serialize;
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    PgConnection,
+};
+
+use crate::types::api::ApiError;
+
+use super::mod_gd_version::{ModGDVersionCreate, VerPlatform};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GDVersion {
+    pub version: String,
+    pub current: bool,
+    pub deprecated: bool,
+    pub platforms: Vec<VerPlatform>,
+    pub created_at: String,
+}
+
+struct GDVersionRecord {
+    version: String,
+    current: bool,
+    deprecated: bool,
+    created_at: DateTime<Utc>,
+}
+
+impl GDVersionRecord {
+    fn into_gd_version(self, platforms: Vec<VerPlatform>) -> GDVersion {
+        GDVersion {
+            version: self.version,
+            current: self.current,
+            deprecated: self.deprecated,
+            platforms,
+            created_at: self.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+impl GDVersion {
+    /**
+     * Newest first. GD versions are decimals, so 2.2 comes after 2.113
+     */
+    pub async fn get_all(pool: &mut PgConnection) -> Result<Vec<GDVersion>, ApiError> {
+        let records = match sqlx::query_as!(
+            GDVersionRecord,
+            "SELECT version, current, deprecated, created_at
+            FROM gd_versions
+            ORDER BY (split_part(version, '.', 1) || '.' || split_part(version, '.', 2))::numeric DESC,
+                version DESC"
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+
+        let platforms = match sqlx::query!(
+            r#"SELECT gd, platform as "platform: VerPlatform"
+            FROM gd_version_platforms
+            ORDER BY platform"#
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+
+        let mut map: HashMap<String, Vec<VerPlatform>> = HashMap::new();
+        for i in platforms {
+            map.entry(i.gd).or_default().push(i.platform);
+        }
+
+        Ok(records
+            .into_iter()
+            .map(|x| {
+                let platforms = map.remove(&x.version).unwrap_or_default();
+                x.into_gd_version(platforms)
+            })
+            .collect())
+    }
+
+    pub async fn get_one(
+        version: &str,
+        pool: &mut PgConnection,
+    ) -> Result<Option<GDVersion>, ApiError> {
+        let record = match sqlx::query_as!(
+            GDVersionRecord,
+            "SELECT version, current, deprecated, created_at
+            FROM gd_versions
+            WHERE version = $1",
+            version
+        )
+        .fetch_optional(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(None) => return Ok(None),
+            Ok(Some(r)) => r,
+        };
+
+        let platforms = match sqlx::query_scalar!(
+            r#"SELECT platform as "platform: VerPlatform"
+            FROM gd_version_platforms
+            WHERE gd = $1
+            ORDER BY platform"#,
+            version
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+
+        Ok(Some(record.into_gd_version(platforms)))
+    }
+
+    /**
+     * Should be called inside a transaction, since it touches multiple tables
+     */
+    pub async fn create(
+        version: &str,
+        current: bool,
+        deprecated: bool,
+        platforms: &[VerPlatform],
+        pool: &mut PgConnection,
+    ) -> Result<GDVersion, ApiError> {
+        if version == "*" || !is_valid_gd_version(version) {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid GD version {}",
+                version
+            )));
+        }
+        if GDVersion::get_one(version, &mut *pool).await?.is_some() {
+            return Err(ApiError::BadRequest(format!(
+                "GD version {} already exists",
+                version
+            )));
+        }
+
+        if current {
+            GDVersion::clear_current(&mut *pool).await?;
+        }
+
+        if let Err(e) = sqlx::query!(
+            "INSERT INTO gd_versions (version, current, deprecated) VALUES ($1, $2, $3)",
+            version,
+            current,
+            deprecated
+        )
+        .execute(&mut *pool)
+        .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+
+        GDVersion::set_platforms(version, platforms, &mut *pool).await?;
+
+        match GDVersion::get_one(version, &mut *pool).await? {
+            None => Err(ApiError::InternalError),
+            Some(v) => Ok(v),
+        }
+    }
+
+    /**
+     * Should be called inside a transaction, since it touches multiple tables
+     */
+    pub async fn update(
+        version: &str,
+        current: Option<bool>,
+        deprecated: Option<bool>,
+        platforms: Option<&[VerPlatform]>,
+        pool: &mut PgConnection,
+    ) -> Result<GDVersion, ApiError> {
+        if GDVersion::get_one(version, &mut *pool).await?.is_none() {
+            return Err(ApiError::NotFound(format!(
+                "GD version {} not found",
+                version
+            )));
+        }
+
+        if let Some(true) = current {
+            GDVersion::clear_current(&mut *pool).await?;
+        }
+
+        if let Err(e) = sqlx::query!(
+            "UPDATE gd_versions
+            SET current = COALESCE($2, current),
+                deprecated = COALESCE($3, deprecated)
+            WHERE version = $1",
+            version,
+            current,
+            deprecated
+        )
+        .execute(&mut *pool)
+        .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+
+        if let Some(p) = platforms {
+            GDVersion::set_platforms(version, p, &mut *pool).await?;
+        }
+
+        match GDVersion::get_one(version, &mut *pool).await? {
+            None => Err(ApiError::InternalError),
+            Some(v) => Ok(v),
+        }
+    }
+
+    /**
+     * Checks a gd version coming from a query string against the registry. "*" is always valid.
+     */
+    pub async fn validate(version: &str, pool: &mut PgConnection) -> Result<(), ApiError> {
+        if version == "*" {
+            return Ok(());
+        }
+        match sqlx::query!(
+            "SELECT version FROM gd_versions WHERE version = $1",
+            version
+        )
+        .fetch_optional(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+            Ok(None) => Err(ApiError::BadRequest(format!(
+                "Invalid gd version {}",
+                version
+            ))),
+            Ok(Some(_)) => Ok(()),
+        }
+    }
+
+    /**
+     * Checks the gd versions of an uploaded mod: they must exist, not be deprecated
+     * and be available on the platforms they're declared for
+     */
+    pub async fn validate_for_platforms(
+        versions: &[ModGDVersionCreate],
+        pool: &mut PgConnection,
+    ) -> Result<(), ApiError> {
+        let registry: HashMap<String, GDVersion> = GDVersion::get_all(&mut *pool)
+            .await?
+            .into_iter()
+            .map(|x| (x.version.clone(), x))
+            .collect();
+
+        for i in versions {
+            if i.gd == "*" {
+                continue;
+            }
+            let gd = match registry.get(&i.gd) {
+                None => {
+                    return Err(ApiError::BadRequest(format!(
+                        "Unknown GD version {} in mod.json",
+                        i.gd
+                    )))
+                }
+                Some(gd) => gd,
+            };
+            if gd.deprecated {
+                return Err(ApiError::BadRequest(format!(
+                    "GD version {} is deprecated and doesn't accept new mods",
+                    gd.version
+                )));
+            }
+            if !gd.platforms.contains(&i.platform) {
+                return Err(ApiError::BadRequest(format!(
+                    "GD version {} isn't available on {}",
+                    gd.version,
+                    i.platform.as_str()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn clear_current(pool: &mut PgConnection) -> Result<(), ApiError> {
+        if let Err(e) = sqlx::query!("UPDATE gd_versions SET current = false WHERE current")
+            .execute(&mut *pool)
+            .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+        Ok(())
+    }
+
+    async fn set_platforms(
+        version: &str,
+        platforms: &[VerPlatform],
+        pool: &mut PgConnection,
+    ) -> Result<(), ApiError> {
+        if let Err(e) = sqlx::query!("DELETE FROM gd_version_platforms WHERE gd = $1", version)
+            .execute(&mut *pool)
+            .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+
+        for platform in VerPlatform::expand(platforms) {
+            if let Err(e) = sqlx::query!(
+                "INSERT INTO gd_version_platforms (gd, platform) VALUES ($1, $2)",
+                version,
+                platform as VerPlatform
+            )
+            .execute(&mut *pool)
+            .await
+            {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_valid_gd_version(version: &str) -> bool {
+    version.len() <= 32
+        && version.contains('.')
+        && version
+            .split('.')
+            .all(|x| !x.is_empty() && x.chars().all(|c| c.is_ascii_digit()))
+}
@@ -7,7 +7,8 @@ use sqlx::{PgConnection, Postgres, QueryBuilder};
 
 use super::{
     dependency::{Dependency, ResponseDependency},
-    mod_gd_version::{GDVersionEnum, VerPlatform},
+    mod_gd_version::VerPlatform,
+    mod_version::ModVersionChannel,
 };
 
 #[derive(sqlx::FromRow, Clone, Debug)]
@@ -54,7 +55,7 @@ pub enum IncompatibilityImportance {
     Superseded,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct ResponseIncompatibility {
     pub mod_id: String,
     pub version: String,
@@ -136,7 +137,7 @@ impl Incompatibility {
     pub async fn get_for_mod_versions(
         ids: &Vec<i32>,
         platform: Option<VerPlatform>,
-        gd: Option<GDVersionEnum>,
+        gd: Option<&str>,
         geode: Option<&semver::Version>,
         pool: &mut PgConnection,
     ) -> Result<HashMap<i32, Vec<FetchedIncompatibility>>, ApiError> {
@@ -149,15 +150,7 @@ impl Incompatibility {
             WHERE mv.id = ANY($1)
             AND ($2 IS NULL OR mgv.gd = $2)
             AND ($3 IS NULL OR mgv.platform = $3)
-            AND ($4 IS NULL OR CASE
-                WHEN SPLIT_PART($4, '-', 2) ILIKE 'alpha%' THEN $4 = mv.geode
-                ELSE SPLIT_PART($4, '.', 1) = SPLIT_PART(mv.geode, '.', 1)
-                    AND SPLIT_PART(mv.geode, '.', 2) <= SPLIT_PART($4, '.', 2)
-                    AND (
-                        SPLIT_PART(mv.geode, '-', 2) = '' 
-                        OR SPLIT_PART(mv.geode, '-', 2) >= SPLIT_PART($4, '-', 2)
-                    )
-            END)
+            AND ($4 IS NULL OR geode_compatible(mv.geode, $4))
             "#,
         )
         .bind(ids)
@@ -184,8 +177,9 @@ impl Incompatibility {
     pub async fn get_supersedes_for(
         ids: &Vec<String>,
         platform: VerPlatform,
-        gd: GDVersionEnum,
+        gd: &str,
         geode: &semver::Version,
+        channel: ModVersionChannel,
         pool: &mut PgConnection,
     ) -> Result<HashMap<String, Replacement>, ApiError> {
         let mut ret: HashMap<String, Replacement> = HashMap::new();
@@ -204,7 +198,7 @@ impl Incompatibility {
                     replacement.id AS replacement_id,
                     ROW_NUMBER() OVER(
                         partition by replacement.mod_id 
-                        order by replacement.version desc
+                        order by replacement.version_key desc nulls last, replacement.id desc
                     ) rn
                 FROM incompatibilities replaced
                 INNER JOIN mod_versions replacement ON replacement.id = replaced.mod_id
@@ -216,19 +210,17 @@ impl Incompatibility {
                 AND replaced.incompatibility_id = ANY($1)
                 AND (replacement_mgv.gd = $2 OR replacement_mgv.gd = '*')
                 AND replacement_mgv.platform = $3
-                AND CASE
-                    WHEN SPLIT_PART($4, '-', 2) ILIKE 'alpha%' THEN $4 = replacement.geode
-                    ELSE SPLIT_PART($4, '.', 1) = SPLIT_PART(replacement.geode, '.', 1)
-                        AND semver_compare(replacement.geode, $4) >= 0
-                END
-                ORDER BY replacement.id DESC, replacement.version DESC
+                AND replacement.channel <= $5
+                AND geode_compatible(replacement.geode, $4)
+                ORDER BY replacement.version_key DESC NULLS LAST, replacement.id DESC
             ) q
             WHERE q.rn = 1
             "#,
             ids,
-            gd as GDVersionEnum,
+            gd,
             platform as VerPlatform,
-            geode.to_string()
+            geode.to_string(),
+            channel as ModVersionChannel
         )
         .fetch_all(&mut *pool)
         .await
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use chrono::{SecondsFormat, Utc};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+
+use crate::types::api::{create_download_link, create_platform_download_link, ApiError};
+
+use super::{
+    dependency::{Dependency, DependencyImportance},
+    mod_gd_version::VerPlatform,
+    mod_version::{ModVersion, ModVersionChannel},
+    mod_version_artifact::ModVersionArtifact,
+    mod_version_status::ModVersionStatusEnum,
+};
+
+pub const MAX_LOCKFILE_MODS: usize = 500;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Lockfile {
+    pub platform: VerPlatform,
+    pub gd: String,
+    pub geode: String,
+    #[serde(default)]
+    pub generated_at: String,
+    pub mods: Vec<LockedMod>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockedMod {
+    pub id: String,
+    pub version: String,
+    pub hash: String,
+    #[serde(default)]
+    pub download_link: String,
+    /// Pulled in as a required dependency of another locked mod
+    #[serde(default)]
+    pub dependency: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LockedModStatus {
+    Ok,
+    Missing,
+    NotAccepted,
+    HashMismatch,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct LockedModVerification {
+    pub id: String,
+    pub version: String,
+    pub status: LockedModStatus,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct LockfileVerification {
+    pub valid: bool,
+    pub mods: Vec<LockedModVerification>,
+}
+
+struct LockedVersionRecord {
+    id: i32,
+    mod_id: String,
+    version: String,
+    hash: String,
+    status: ModVersionStatusEnum,
+}
+
+impl Lockfile {
+    /**
+     * Pins the given versions and every required dependency they resolve to.
+     * The versions have to be resolved for the same platform, gd, geode and channel.
+     */
+    pub async fn create(
+        versions: Vec<ModVersion>,
+        platform: VerPlatform,
+        gd: &str,
+        geode: &Version,
+        channel: ModVersionChannel,
+        app_url: &str,
+        pool: &mut PgConnection,
+    ) -> Result<Lockfile, ApiError> {
+        let roots: Vec<i32> = versions.iter().map(|x| x.id).collect();
+        // mod id -> (version id, version, pulled in as a dependency)
+        let mut locked: HashMap<String, (i32, String, bool)> = versions
+            .into_iter()
+            .map(|x| (x.mod_id, (x.id, x.version, false)))
+            .collect();
+
+        let deps = if roots.is_empty() {
+            HashMap::new()
+        } else {
+            Dependency::get_for_mod_versions(
+                &roots,
+                Some(platform),
+                Some(gd),
+                Some(geode),
+                channel,
+                pool,
+            )
+            .await?
+        };
+        for dep in deps.into_values().flatten() {
+            if dep.importance != DependencyImportance::Required {
+                continue;
+            }
+            match locked.get(&dep.dependency_id) {
+                // Two mods can resolve the same dependency differently, keep the newest one
+                Some((_, version, true)) if newer(&dep.version, version) => {}
+                Some(_) => continue,
+                None => {}
+            }
+            locked.insert(
+                dep.dependency_id.clone(),
+                (dep.mod_version_id, dep.version.clone(), true),
+            );
+        }
+
+        let ids: Vec<i32> = locked.values().map(|x| x.0).collect();
+        let mut records = Lockfile::get_versions(&ids, pool).await?;
+        let mut artifacts = ModVersionArtifact::get_for_mod_versions(&ids, platform, pool).await?;
+
+        let mut mods: Vec<LockedMod> = vec![];
+        for (mod_id, (id, version, dependency)) in locked {
+            let (hash, download_link) = match (artifacts.remove(&id), records.remove(&id)) {
+                (Some(artifact), _) => (
+                    artifact.hash,
+                    create_platform_download_link(app_url, &mod_id, &version, platform),
+                ),
+                (None, Some(record)) => (
+                    record.hash,
+                    create_download_link(app_url, &mod_id, &version),
+                ),
+                (None, None) => return Err(ApiError::InternalError),
+            };
+            mods.push(LockedMod {
+                id: mod_id,
+                version,
+                hash,
+                download_link,
+                dependency,
+            });
+        }
+        mods.sort_by(|a, b| a.dependency.cmp(&b.dependency).then(a.id.cmp(&b.id)));
+
+        Ok(Lockfile {
+            platform,
+            gd: gd.to_string(),
+            geode: geode.to_string(),
+            generated_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            mods,
+        })
+    }
+
+    /**
+     * Checks that every locked version still exists, is accepted and has the same hash
+     */
+    pub async fn verify(&self, pool: &mut PgConnection) -> Result<LockfileVerification, ApiError> {
+        let mod_ids: Vec<String> = self.mods.iter().map(|x| x.id.clone()).collect();
+        let records = match sqlx::query_as!(
+            LockedVersionRecord,
+            r#"SELECT mv.id, mv.mod_id, mv.version, mv.hash, mvs.status as "status: _"
+            FROM mod_versions mv
+            INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+            WHERE mv.mod_id = ANY($1)"#,
+            &mod_ids
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+
+        let ids: Vec<i32> = records.iter().map(|x| x.id).collect();
+        let artifacts = ModVersionArtifact::get_for_mod_versions(&ids, self.platform, pool).await?;
+        let records: HashMap<(&str, &str), &LockedVersionRecord> = records
+            .iter()
+            .map(|x| ((x.mod_id.as_str(), x.version.as_str()), x))
+            .collect();
+
+        let mods: Vec<LockedModVerification> = self
+            .mods
+            .iter()
+            .map(|locked| {
+                let key = (locked.id.as_str(), locked.version.trim_start_matches('v'));
+                let status = match records.get(&key) {
+                    None => LockedModStatus::Missing,
+                    Some(r) if r.status != ModVersionStatusEnum::Accepted => {
+                        LockedModStatus::NotAccepted
+                    }
+                    Some(r) => {
+                        let hash = artifacts.get(&r.id).map_or(&r.hash, |a| &a.hash);
+                        if *hash == locked.hash {
+                            LockedModStatus::Ok
+                        } else {
+                            LockedModStatus::HashMismatch
+                        }
+                    }
+                };
+                LockedModVerification {
+                    id: locked.id.clone(),
+                    version: locked.version.clone(),
+                    status,
+                }
+            })
+            .collect();
+
+        Ok(LockfileVerification {
+            valid: mods.iter().all(|x| x.status == LockedModStatus::Ok),
+            mods,
+        })
+    }
+
+    async fn get_versions(
+        ids: &Vec<i32>,
+        pool: &mut PgConnection,
+    ) -> Result<HashMap<i32, LockedVersionRecord>, ApiError> {
+        match sqlx::query_as!(
+            LockedVersionRecord,
+            r#"SELECT mv.id, mv.mod_id, mv.version, mv.hash, mvs.status as "status: _"
+            FROM mod_versions mv
+            INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+            WHERE mv.id = ANY($1)"#,
+            ids
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+            Ok(r) => Ok(r.into_iter().map(|x| (x.id, x)).collect()),
+        }
+    }
+}
+
+fn newer(a: &str, b: &str) -> bool {
+    match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => a > b,
+        _ => false,
+    }
+}
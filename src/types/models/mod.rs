@@ -1,11 +1,19 @@
+pub mod collection;
 pub mod dependency;
 pub mod developer;
+pub mod developer_stats;
+pub mod gd_version;
 pub mod download;
+pub mod download_stats;
 pub mod github_login_attempt;
 pub mod incompatibility;
+pub mod lockfile;
+pub mod mod_deprecation;
 pub mod mod_entity;
 pub mod mod_gd_version;
 pub mod mod_version;
+pub mod mod_version_artifact;
+pub mod mod_version_diff;
 pub mod mod_version_status;
 pub mod tag;
 pub mod stats;
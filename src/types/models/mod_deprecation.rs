@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use chrono::SecondsFormat;
+use serde::Serialize;
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    PgConnection,
+};
+
+use crate::types::api::ApiError;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ModDeprecation {
+    pub reason: String,
+    pub successor: Option<String>,
+    pub created_at: String,
+}
+
+struct ModDeprecationRecord {
+    mod_id: String,
+    reason: String,
+    successor_id: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl ModDeprecationRecord {
+    fn into_deprecation(self) -> ModDeprecation {
+        ModDeprecation {
+            reason: self.reason,
+            successor: self.successor_id,
+            created_at: self.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+impl ModDeprecation {
+    pub async fn get_for_mod(
+        mod_id: &str,
+        pool: &mut PgConnection,
+    ) -> Result<Option<ModDeprecation>, ApiError> {
+        match sqlx::query_as!(
+            ModDeprecationRecord,
+            "SELECT mod_id, reason, successor_id, created_at
+            FROM mod_deprecations
+            WHERE mod_id = $1",
+            mod_id
+        )
+        .fetch_optional(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+            Ok(r) => Ok(r.map(|x| x.into_deprecation())),
+        }
+    }
+
+    pub async fn get_for_mods(
+        ids: &Vec<String>,
+        pool: &mut PgConnection,
+    ) -> Result<HashMap<String, ModDeprecation>, ApiError> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let records = match sqlx::query_as!(
+            ModDeprecationRecord,
+            "SELECT mod_id, reason, successor_id, created_at
+            FROM mod_deprecations
+            WHERE mod_id = ANY($1)",
+            ids
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+
+        let mut ret: HashMap<String, ModDeprecation> = HashMap::new();
+        for record in records {
+            ret.insert(record.mod_id.clone(), record.into_deprecation());
+        }
+        Ok(ret)
+    }
+
+    /**
+     * Deprecates a mod, or replaces the existing deprecation if there's already one
+     */
+    pub async fn set_for_mod(
+        mod_id: &str,
+        reason: &str,
+        successor: Option<&str>,
+        developer_id: i32,
+        pool: &mut PgConnection,
+    ) -> Result<(), ApiError> {
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(ApiError::BadRequest(
+                "Deprecation reason can't be empty".to_string(),
+            ));
+        }
+        if reason.chars().count() > 1000 {
+            return Err(ApiError::BadRequest(
+                "Deprecation reason too long (max 1000 characters)".to_string(),
+            ));
+        }
+
+        if let Some(s) = successor {
+            if s == mod_id {
+                return Err(ApiError::BadRequest(
+                    "A mod can't be its own successor".to_string(),
+                ));
+            }
+            match sqlx::query!("SELECT id FROM mods WHERE id = $1", s)
+                .fetch_optional(&mut *pool)
+                .await
+            {
+                Err(e) => {
+                    log::error!("{}", e);
+                    return Err(ApiError::DbError);
+                }
+                Ok(None) => {
+                    return Err(ApiError::BadRequest(format!(
+                        "Successor mod {} doesn't exist",
+                        s
+                    )))
+                }
+                Ok(Some(_)) => {}
+            }
+
+            // Following the successors of the successor must not lead back to this mod
+            let cycle = match sqlx::query_scalar!(
+                r#"WITH RECURSIVE chain AS (
+                    SELECT mod_id, successor_id FROM mod_deprecations WHERE mod_id = $1
+                    UNION
+                    SELECT d.mod_id, d.successor_id FROM mod_deprecations d
+                    INNER JOIN chain c ON d.mod_id = c.successor_id
+                )
+                SELECT EXISTS(SELECT 1 FROM chain WHERE successor_id = $2) as "exists!""#,
+                s,
+                mod_id
+            )
+            .fetch_one(&mut *pool)
+            .await
+            {
+                Err(e) => {
+                    log::error!("{}", e);
+                    return Err(ApiError::DbError);
+                }
+                Ok(c) => c,
+            };
+            if cycle {
+                return Err(ApiError::BadRequest(format!(
+                    "Mod {} already leads back to {} through its successors",
+                    s, mod_id
+                )));
+            }
+        }
+
+        if let Err(e) = sqlx::query!(
+            "INSERT INTO mod_deprecations (mod_id, reason, successor_id, developer_id)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (mod_id) DO UPDATE
+            SET reason = excluded.reason,
+                successor_id = excluded.successor_id,
+                developer_id = excluded.developer_id,
+                created_at = now()",
+            mod_id,
+            reason,
+            successor,
+            developer_id
+        )
+        .execute(&mut *pool)
+        .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_for_mod(mod_id: &str, pool: &mut PgConnection) -> Result<(), ApiError> {
+        match sqlx::query!("DELETE FROM mod_deprecations WHERE mod_id = $1", mod_id)
+            .execute(&mut *pool)
+            .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+            Ok(r) => {
+                if r.rows_affected() == 0 {
+                    return Err(ApiError::NotFound(format!(
+                        "Mod {} isn't deprecated",
+                        mod_id
+                    )));
+                }
+                Ok(())
+            }
+        }
+    }
+}
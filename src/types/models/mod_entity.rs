@@ -1,23 +1,24 @@
 use crate::{
     endpoints::{
         developers::{SimpleDevMod, SimpleDevModVersion},
-        mods::{IndexQueryParams, IndexSortType},
+        mods::{IndexQueryParams, IndexSortType, TagsMode},
     },
     types::{
-        api::{ApiError, PaginatedData},
+        api::{decode_cursor, encode_cursor, ApiError, PaginatedData},
+        geode_compat::{parse_loader_version, push_compatible_filter},
         mod_json::{self, ModJson},
         models::{
-            dependency::{Dependency, FetchedDependency},
+            dependency::{Dependency, DependencyImportance, FetchedDependency},
             incompatibility::{FetchedIncompatibility, Incompatibility},
-            mod_version::ModVersion, mod_version_status::ModVersionStatusEnum,
+            mod_version::{ModVersion, ModVersionChannel},
+            mod_version_status::ModVersionStatusEnum,
         },
     },
 };
 use actix_web::web::Bytes;
 use chrono::SecondsFormat;
 use reqwest::Client;
-use semver::Version;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{
     types::chrono::{DateTime, Utc},
     PgConnection, Postgres, QueryBuilder,
@@ -28,7 +29,8 @@ use super::{
     dependency::ResponseDependency,
     developer::{Developer, FetchedDeveloper},
     incompatibility::{Replacement, ResponseIncompatibility},
-    mod_gd_version::{DetailedGDVersion, GDVersionEnum, ModGDVersion, VerPlatform},
+    mod_deprecation::ModDeprecation,
+    mod_gd_version::{DetailedGDVersion, ModGDVersion, VerPlatform},
     tag::Tag,
 };
 
@@ -43,18 +45,24 @@ pub struct Mod {
     pub tags: Vec<String>,
     pub about: Option<String>,
     pub changelog: Option<String>,
+    pub deprecation: Option<ModDeprecation>,
     pub created_at: String,
     pub updated_at: String,
+    /// Highlighted excerpt of the description and about, only set when searching
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
 pub struct ModUpdate {
     pub id: String,
-    pub version: String,
+    /// None for deprecated mods that have no compatible version
+    pub version: Option<String>,
     #[serde(skip_serializing)]
     pub mod_version_id: i32,
-    pub download_link: String,
+    pub download_link: Option<String>,
     pub replacement: Option<Replacement>,
+    pub deprecation: Option<ModDeprecation>,
     pub dependencies: Vec<ResponseDependency>,
     pub incompatibilities: Vec<ResponseIncompatibility>,
 }
@@ -70,6 +78,248 @@ struct ModRecord {
     changelog: Option<String>,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
+    #[sqlx(default)]
+    snippet: Option<String>,
+    #[sqlx(default)]
+    name: String,
+    #[sqlx(default)]
+    score: f32,
+    #[sqlx(default)]
+    trending_score: f64,
+}
+
+/**
+ * Position of the last mod of an index page, the key depends on the sort
+ */
+#[derive(Serialize, Deserialize)]
+struct ModIndexCursor {
+    key: ModIndexCursorKey,
+    id: String,
+    /// The first page fell back to fuzzy search
+    #[serde(default)]
+    fuzzy: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ModIndexCursorKey {
+    Downloads(i32),
+    /// Microseconds since the epoch
+    Timestamp(i64),
+    Name(String),
+    /// Search rank, then downloads
+    Score(f32, i32),
+    /// Trending score, then downloads
+    Trending(f64, i32),
+}
+
+#[derive(Serialize)]
+pub struct ModIndex {
+    #[serde(flatten)]
+    pub page: PaginatedData<Mod>,
+    /// Counts of matching mods per value, keyed by facet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facets: Option<HashMap<IndexFacet, HashMap<String, i64>>>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexFacet {
+    Tags,
+    Platforms,
+    Gd,
+    Featured,
+}
+
+impl IndexFacet {
+    fn parse_list(facets: &str) -> Result<Vec<IndexFacet>, ApiError> {
+        let mut ret: Vec<IndexFacet> = vec![];
+        for i in facets.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            let facet = match i {
+                "tags" => IndexFacet::Tags,
+                "platforms" => IndexFacet::Platforms,
+                "gd" => IndexFacet::Gd,
+                "featured" => IndexFacet::Featured,
+                _ => return Err(ApiError::BadRequest(format!("Invalid facet {}", i))),
+            };
+            if !ret.contains(&facet) {
+                ret.push(facet);
+            }
+        }
+        Ok(ret)
+    }
+}
+
+/**
+ * Parsed mod index filters, shared by the page, count and facet queries
+ */
+struct IndexFilters {
+    tags: Vec<i32>,
+    tags_mode: TagsMode,
+    exclude_tags: Vec<i32>,
+    featured: Option<bool>,
+    deprecated: Option<bool>,
+    developer_id: Option<i32>,
+    status: ModVersionStatusEnum,
+    search: Option<String>,
+    /// Match the search by trigram similarity instead of full text
+    fuzzy: bool,
+    geode: Option<semver::Version>,
+    gd: Option<String>,
+    platforms: Vec<VerPlatform>,
+    depends_on: Option<String>,
+    depends_on_importance: Option<DependencyImportance>,
+    incompatible_with: Option<String>,
+    channel: ModVersionChannel,
+}
+
+/// Id of the latest accepted version of mod m, which is what dependency filters look at
+const LATEST_ACCEPTED_VERSION: &str = "(SELECT lv.id FROM mod_versions lv
+    INNER JOIN mod_version_statuses lvs ON lvs.mod_version_id = lv.id
+    WHERE lv.mod_id = m.id AND lvs.status = 'accepted'
+    ORDER BY lv.version_key DESC NULLS LAST, lv.id DESC LIMIT 1)";
+
+impl IndexFilters {
+    /**
+     * Pushes the WHERE clause for mods m, mod_versions mv, mod_version_statuses mvs and mod_gd_versions mgv.
+     * The filter of the skipped facet is left out, so its counts show what picking another value would give.
+     */
+    fn push_where(&self, builder: &mut QueryBuilder<'_, Postgres>, skip: Option<IndexFacet>) {
+        builder.push("WHERE mvs.status = ");
+        builder.push_bind(self.status);
+
+        // Pending versions are reviewed whatever channel they're on
+        if self.status == ModVersionStatusEnum::Accepted {
+            builder.push(" AND mv.channel <= ");
+            builder.push_bind(self.channel);
+        }
+
+        if !self.tags.is_empty() && skip != Some(IndexFacet::Tags) {
+            builder.push(match self.tags_mode {
+                TagsMode::Any => " AND EXISTS (SELECT 1 FROM mods_mod_tags mmt WHERE mmt.mod_id = m.id AND mmt.tag_id = ANY(",
+                // No requested tag may be missing from the mod
+                TagsMode::All => " AND NOT EXISTS (SELECT 1 FROM unnest(",
+            });
+            builder.push_bind(self.tags.clone());
+            builder.push(match self.tags_mode {
+                TagsMode::Any => "))",
+                TagsMode::All => ") t(id) WHERE NOT EXISTS (SELECT 1 FROM mods_mod_tags mmt WHERE mmt.mod_id = m.id AND mmt.tag_id = t.id))",
+            });
+        }
+
+        if !self.exclude_tags.is_empty() {
+            builder.push(" AND NOT EXISTS (SELECT 1 FROM mods_mod_tags mmt WHERE mmt.mod_id = m.id AND mmt.tag_id = ANY(");
+            builder.push_bind(self.exclude_tags.clone());
+            builder.push("))");
+        }
+
+        if let Some(f) = self.featured.filter(|_| skip != Some(IndexFacet::Featured)) {
+            builder.push(" AND m.featured = ");
+            builder.push_bind(f);
+        }
+
+        if let Some(d) = self.deprecated {
+            builder.push(if d { " AND EXISTS" } else { " AND NOT EXISTS" });
+            builder.push(" (SELECT 1 FROM mod_deprecations mdp WHERE mdp.mod_id = m.id)");
+        }
+
+        if let Some(id) = self.developer_id {
+            builder.push(" AND EXISTS (SELECT 1 FROM mods_developers md WHERE md.mod_id = m.id AND md.developer_id = ");
+            builder.push_bind(id);
+            builder.push(")");
+        }
+
+        if let Some(dependency) = &self.depends_on {
+            builder.push(" AND EXISTS (SELECT 1 FROM dependencies dp WHERE dp.dependency_id = ");
+            builder.push_bind(dependency.clone());
+            if let Some(importance) = self.depends_on_importance {
+                builder.push(" AND dp.importance = ");
+                builder.push_bind(importance);
+            }
+            builder.push(" AND dp.dependent_id = ");
+            builder.push(LATEST_ACCEPTED_VERSION);
+            builder.push(")");
+        }
+
+        if let Some(incompatibility) = &self.incompatible_with {
+            builder.push(" AND EXISTS (SELECT 1 FROM incompatibilities ic WHERE ic.incompatibility_id = ");
+            builder.push_bind(incompatibility.clone());
+            builder.push(" AND ic.mod_id = ");
+            builder.push(LATEST_ACCEPTED_VERSION);
+            builder.push(")");
+        }
+
+        // The name match catches partial words that full text search doesn't
+        if let Some(s) = self.search.as_ref().filter(|_| !self.fuzzy) {
+            builder.push(" AND (m.search_vector @@ websearch_to_tsquery('english', ");
+            builder.push_bind(s.clone());
+            builder.push(") OR mv.name ILIKE ");
+            builder.push_bind(format!("%{}%", s.to_lowercase()));
+            builder.push(")");
+        } else if let Some(s) = &self.search {
+            builder.push(" AND (mv.name % ");
+            builder.push_bind(s.clone());
+            builder.push(" OR ");
+            builder.push_bind(s.clone());
+            builder.push(" <% mv.name)");
+        }
+
+        if let Some(geode) = &self.geode {
+            push_compatible_filter(builder, "mv.geode", geode);
+        }
+
+        if let Some(g) = self.gd.as_ref().filter(|_| skip != Some(IndexFacet::Gd)) {
+            builder.push(" AND (mgv.gd = ");
+            builder.push_bind(g.clone());
+            builder.push(" OR mgv.gd = '*')");
+        }
+
+        if !self.platforms.is_empty() && skip != Some(IndexFacet::Platforms) {
+            builder.push(" AND mgv.platform IN (");
+            let mut separated = builder.separated(", ");
+            for platform in &self.platforms {
+                separated.push_bind(*platform);
+            }
+            builder.push(")");
+        }
+    }
+
+    async fn get_facet_counts(
+        &self,
+        facet: IndexFacet,
+        pool: &mut PgConnection,
+    ) -> Result<HashMap<String, i64>, ApiError> {
+        let (value, join) = match facet {
+            IndexFacet::Tags => (
+                "ft.name",
+                "INNER JOIN mods_mod_tags fmt ON fmt.mod_id = m.id INNER JOIN mod_tags ft ON ft.id = fmt.tag_id ",
+            ),
+            IndexFacet::Platforms => ("mgv.platform::text", ""),
+            IndexFacet::Gd => ("mgv.gd", ""),
+            IndexFacet::Featured => ("m.featured::text", ""),
+        };
+        let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(format!(
+            "SELECT {} AS value, COUNT(DISTINCT m.id) AS count FROM mods m
+            INNER JOIN mod_versions mv ON m.id = mv.mod_id
+            INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+            INNER JOIN mod_gd_versions mgv ON mgv.mod_id = mv.id {}",
+            value, join
+        ));
+        self.push_where(&mut builder, Some(facet));
+        builder.push(" GROUP BY 1");
+
+        match builder
+            .build_query_as::<(String, i64)>()
+            .fetch_all(&mut *pool)
+            .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+            Ok(r) => Ok(r.into_iter().collect()),
+        }
+    }
 }
 
 #[derive(sqlx::FromRow)]
@@ -90,6 +340,7 @@ struct ModRecordGetOne {
     api: bool,
     mod_id: String,
     status: ModVersionStatusEnum,
+    channel: ModVersionChannel,
     about: Option<String>,
     changelog: Option<String>,
     created_at: DateTime<Utc>,
@@ -108,6 +359,19 @@ pub struct ModStats {
     pub total_downloads: i64,
 }
 
+#[derive(Serialize, Debug, Clone)]
+pub struct WeeklyTopMod {
+    pub id: String,
+    pub name: String,
+    pub weekly_downloads: i32,
+}
+
+#[derive(Serialize, Debug, sqlx::FromRow)]
+pub struct ModSuggestion {
+    pub id: String,
+    pub name: String,
+}
+
 impl Mod {
     pub async fn get_stats(pool: &mut PgConnection) -> Result<ModStats, ApiError> {
         match sqlx::query!("
@@ -137,14 +401,125 @@ impl Mod {
         }
     }
 
+    /**
+     * Quick name lookup for search-as-you-type, prefix matches first and then by trigram similarity
+     */
+    pub async fn suggest(
+        query: &str,
+        limit: i64,
+        pool: &mut PgConnection,
+    ) -> Result<Vec<ModSuggestion>, ApiError> {
+        let contains = format!("%{}%", query);
+        let prefix = format!("{}%", query);
+        match sqlx::query_as!(
+            ModSuggestion,
+            r#"SELECT q.id, q.name
+            FROM (
+                SELECT m.id, mv.name, m.download_count,
+                    GREATEST(word_similarity($1, mv.name), word_similarity($1, m.id)) AS score,
+                    row_number() over (partition by m.id order by mv.version_key desc nulls last, mv.id desc) rn
+                FROM mods m
+                INNER JOIN mod_versions mv ON mv.mod_id = m.id
+                INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+                WHERE mvs.status = 'accepted'
+                AND (mv.name ILIKE $2 OR m.id ILIKE $2 OR $1 <% mv.name OR $1 <% m.id)
+            ) q
+            WHERE q.rn = 1
+            ORDER BY q.name ILIKE $3 DESC, q.score DESC, q.download_count DESC
+            LIMIT $4"#,
+            query,
+            contains,
+            prefix,
+            limit
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+            Ok(r) => Ok(r),
+        }
+    }
+
+    /**
+     * Most downloaded mods of the last week for every tag, or only the given tags.
+     * Weekly downloads are refreshed by the trending job.
+     */
+    pub async fn get_weekly_top(
+        tags: &[i32],
+        limit: i64,
+        pool: &mut PgConnection,
+    ) -> Result<HashMap<String, Vec<WeeklyTopMod>>, ApiError> {
+        struct WeeklyTopRecord {
+            tag: String,
+            id: String,
+            name: String,
+            weekly_download_count: i32,
+        }
+
+        let records = match sqlx::query_as!(
+            WeeklyTopRecord,
+            r#"SELECT q.tag as "tag!", q.id as "id!", q.name as "name!", q.weekly_download_count as "weekly_download_count!"
+            FROM (
+                SELECT mt.name AS tag, m.id, lv.name, m.weekly_download_count,
+                    row_number() over (partition by mt.id order by m.weekly_download_count desc, m.download_count desc, m.id) rn
+                FROM mods m
+                INNER JOIN mods_mod_tags mmt ON mmt.mod_id = m.id
+                INNER JOIN mod_tags mt ON mt.id = mmt.tag_id
+                INNER JOIN LATERAL (
+                    SELECT mv.name FROM mod_versions mv
+                    INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+                    WHERE mv.mod_id = m.id AND mvs.status = 'accepted'
+                    ORDER BY mv.version_key DESC NULLS LAST, mv.id DESC
+                    LIMIT 1
+                ) lv ON true
+                WHERE m.weekly_download_count > 0
+                AND (cardinality($1::int[]) = 0 OR mt.id = ANY($1))
+            ) q
+            WHERE q.rn <= $2
+            ORDER BY q.tag, q.rn"#,
+            tags,
+            limit
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+
+        let mut ret: HashMap<String, Vec<WeeklyTopMod>> = HashMap::new();
+        for i in records {
+            ret.entry(i.tag).or_default().push(WeeklyTopMod {
+                id: i.id,
+                name: i.name,
+                weekly_downloads: i.weekly_download_count,
+            });
+        }
+        Ok(ret)
+    }
+
     pub async fn get_index(
         pool: &mut PgConnection,
         query: IndexQueryParams,
-    ) -> Result<PaginatedData<Mod>, ApiError> {
+    ) -> Result<ModIndex, ApiError> {
         let tags = match query.tags {
             Some(t) => Tag::parse_tags(&t, pool).await?,
             None => vec![],
         };
+        let exclude_tags = match query.exclude_tags {
+            Some(t) => Tag::parse_tags(&t, pool).await?,
+            None => vec![],
+        };
+        let facets = match query.facets {
+            Some(f) => IndexFacet::parse_list(&f)?,
+            None => vec![],
+        };
         let page: i64 = query.page.unwrap_or(1).max(1);
         let per_page = query.per_page.unwrap_or(10).clamp(1, 100);
 
@@ -170,233 +545,287 @@ impl Mod {
                 }
             }
         }
-        let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
-            r#"SELECT q.id, q.repository, q.about, q.changelog, q.download_count, q.featured, q.created_at, q.updated_at, q.status
-            FROM (SELECT m.id, m.repository, m.about, m.changelog, m.download_count, m.featured, m.created_at, m.updated_at, mvs.status,
-            row_number() over (partition by m.id order by mv.id desc) rn FROM mods m
-            INNER JOIN mod_versions mv ON m.id = mv.mod_id
-            INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
-            INNER JOIN mod_gd_versions mgv ON mgv.mod_id = mv.id "#
-        );
-        let mut counter_builder: QueryBuilder<Postgres> = QueryBuilder::new(
-            "SELECT COUNT(DISTINCT m.id) FROM mods m
-            INNER JOIN mod_versions mv ON m.id = mv.mod_id
-            INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
-            INNER JOIN mod_gd_versions mgv ON mgv.mod_id = mv.id ",
-        );
-
-        if !tags.is_empty() {
-            let sql = "INNER JOIN mods_mod_tags mmt ON mmt.mod_id = m.id ";
-            builder.push(sql);
-            counter_builder.push(sql);
-        }
-
-        if query.developer.is_some() {
-            let sql = "INNER JOIN mods_developers md ON md.mod_id = m.id ";
-            builder.push(sql);
-            counter_builder.push(sql);
-        }
-
-        builder.push("WHERE ");
-        counter_builder.push("WHERE ");
-
-        if !tags.is_empty() {
-            let sql = "mmt.tag_id = ANY(";
-            builder.push(sql);
-            counter_builder.push(sql);
-
-            builder.push_bind(&tags); 
-            counter_builder.push_bind(&tags);
-            let sql = ") AND ";
-            builder.push(sql);
-            counter_builder.push(sql);
-        }
-
-        if let Some(f) = query.featured {
-            let sql = "m.featured = ";
-            builder.push(sql);
-            counter_builder.push(sql);
-            builder.push_bind(f);
-            counter_builder.push_bind(f);
-            let sql = " AND ";
-            builder.push(sql);
-            counter_builder.push(sql);
-        }
+        let search = query
+            .query
+            .as_deref()
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(String::from);
 
         let developer = match query.developer {
             Some(d) => match Developer::find_by_username(&d, pool).await? {
                 Some(d) => Some(d),
                 None => {
-                    return Ok(PaginatedData {
-                        data: vec![],
-                        count: 0,
+                    return Ok(ModIndex {
+                        page: PaginatedData {
+                            data: vec![],
+                            count: 0,
+                            next_cursor: None,
+                        },
+                        facets: (!facets.is_empty())
+                            .then(|| facets.iter().map(|f| (*f, HashMap::new())).collect()),
                     })
                 }
             },
             None => None,
         };
 
-        if let Some(d) = developer {
-            let sql = "md.developer_id = ";
-            builder.push(sql);
-            counter_builder.push(sql);
-            builder.push_bind(d.id);
-            counter_builder.push_bind(d.id);
-            let sql = " AND ";
-            builder.push(sql);
-            counter_builder.push(sql);
-        }
+        let sort = match (query.sort, &search) {
+            (IndexSortType::Relevance, None) => IndexSortType::Downloads,
+            (s, _) => s,
+        };
+        // Every sort is keyed on its own columns followed by the mod id, so pages are stable
+        let (sort_columns, descending) = match sort {
+            IndexSortType::Downloads => (vec!["q.download_count"], true),
+            IndexSortType::RecentlyUpdated => (vec!["q.updated_at"], true),
+            IndexSortType::RecentlyPublished => (vec!["q.created_at"], true),
+            IndexSortType::Name => (vec!["q.name"], false),
+            IndexSortType::NameReverse => (vec!["q.name"], true),
+            IndexSortType::Relevance => (vec!["q.score", "q.download_count"], true),
+            IndexSortType::Trending => (vec!["q.trending_score", "q.download_count"], true),
+        };
 
-        let sql = "mvs.status = ";
-        builder.push(sql);
-        counter_builder.push(sql);
+        let cursor: Option<ModIndexCursor> = match &query.cursor {
+            Some(c) => Some(decode_cursor(c)?),
+            None => None,
+        };
+        if let Some(c) = &cursor {
+            let matches = matches!(
+                (sort, &c.key),
+                (IndexSortType::Downloads, ModIndexCursorKey::Downloads(_))
+                    | (IndexSortType::RecentlyUpdated, ModIndexCursorKey::Timestamp(_))
+                    | (IndexSortType::RecentlyPublished, ModIndexCursorKey::Timestamp(_))
+                    | (IndexSortType::Name, ModIndexCursorKey::Name(_))
+                    | (IndexSortType::NameReverse, ModIndexCursorKey::Name(_))
+                    | (IndexSortType::Relevance, ModIndexCursorKey::Score(..))
+                    | (IndexSortType::Trending, ModIndexCursorKey::Trending(..))
+            );
+            if !matches {
+                return Err(ApiError::BadRequest(
+                    "Cursor doesn't match the sort order".to_string(),
+                ));
+            }
+        }
 
         let status = query.status.unwrap_or(ModVersionStatusEnum::Accepted);
-        builder.push_bind(status);
-        counter_builder.push_bind(status);
-
-        let sql = " AND mv.name ILIKE ";
-        builder.push(sql);
-        counter_builder.push(sql);
-
-        let query_string = format!("%{}%", query.query.unwrap_or("".to_string()).to_lowercase());
-        counter_builder.push_bind(&query_string);
-        builder.push_bind(&query_string);
-
-        if let Some(ref geode) = query.geode {
-            let geode = geode.trim_start_matches('v').to_string();
-            if let Ok(parsed) = Version::parse(&geode) {
-                // If alpha, match exactly that version
-                if parsed.pre.contains("alpha") {
-                    let sql = " AND mv.geode = ";
-                    builder.push(sql);
-                    counter_builder.push(sql);
-                    builder.push_bind(parsed.to_string());
-                    counter_builder.push_bind(parsed.to_string());
+        let mut filters = IndexFilters {
+            tags,
+            tags_mode: query.tags_mode,
+            exclude_tags,
+            featured: query.featured,
+            deprecated: query.deprecated,
+            developer_id: developer.map(|d| d.id),
+            status,
+            fuzzy: cursor.as_ref().is_some_and(|c| c.fuzzy),
+            search,
+            geode: query.geode.as_deref().and_then(parse_loader_version),
+            gd: query.gd.clone(),
+            platforms: platforms.clone(),
+            depends_on: query
+                .depends_on
+                .as_deref()
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(String::from),
+            depends_on_importance: query.depends_on_importance,
+            incompatible_with: query
+                .incompatible_with
+                .as_deref()
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(String::from),
+            channel: query.channel,
+        };
+        let (records, count) = loop {
+            let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+                "SELECT q.id, q.repository, q.about, q.changelog, q.download_count, q.featured, q.created_at, q.updated_at, q.status, q.name, q.trending_score",
+            );
+            if sort == IndexSortType::Relevance {
+                builder.push(", q.score");
+            }
+            if let Some(s) = filters.search.as_ref().filter(|_| !filters.fuzzy) {
+                // Escape the text first so the <mark> tags are the only markup in the snippet
+                builder.push(
+                    ", ts_headline('english', replace(replace(replace(replace(replace(
+                        COALESCE(q.description, '') || ' ' || COALESCE(q.about, ''),
+                        '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '\"', '&quot;'), '''', '&#39;'),
+                    websearch_to_tsquery('english', ",
+                );
+                builder.push_bind(s.clone());
+                builder.push(
+                    "), 'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MinWords=5, MaxWords=20') AS snippet",
+                );
+            }
+            builder.push(
+                " FROM (SELECT m.id, m.repository, m.about, m.changelog, m.download_count, m.featured, m.created_at, m.updated_at, m.trending_score, mvs.status, mv.description, mv.name, ",
+            );
+            if let (IndexSortType::Relevance, Some(s)) = (sort, &filters.search) {
+                if filters.fuzzy {
+                    builder.push("word_similarity(");
+                    builder.push_bind(s.clone());
+                    builder.push(", mv.name) AS score, ");
                 } else {
-                    let sql = " AND (SPLIT_PART(mv.geode, '.', 1) = ";
-                    builder.push(sql);
-                    counter_builder.push(sql);
-                    builder.push_bind(parsed.major.to_string());
-                    counter_builder.push_bind(parsed.major.to_string());
-
-                    let sql = " AND SPLIT_PART(mv.geode, '-', 2) NOT LIKE 'alpha%' AND SPLIT_PART(mv.geode, '.', 2) <= ";
-                    builder.push(sql);
-                    counter_builder.push(sql);
-                    builder.push_bind(parsed.minor.to_string());
-                    counter_builder.push_bind(parsed.minor.to_string());
-
-                    // Match only higher betas (or no beta)
-                    if parsed.pre.contains("beta") {
-                        let sql = " AND (SPLIT_PART(mv.geode, '-', 2) = ''
-                            OR SPLIT_PART(mv.geode, '-', 2) <=";
-                        builder.push(sql);
-                        counter_builder.push(sql);
-                        builder.push_bind(parsed.pre.to_string());
-                        counter_builder.push_bind(parsed.pre.to_string());
-                        builder.push(")");
-                        counter_builder.push(")");
-                    }
-
-                    builder.push(")");
-                    counter_builder.push(")");
+                    builder.push("ts_rank_cd(m.search_vector, websearch_to_tsquery('english', ");
+                    builder.push_bind(s.clone());
+                    builder.push(")) AS score, ");
                 }
             }
-        }
-
-        if let Some(g) = query.gd {
-            let sql = " AND (mgv.gd = ";
-            builder.push(sql);
-            builder.push_bind(g);
-            counter_builder.push(sql);
-            counter_builder.push_bind(g);
-            let sql = " OR mgv.gd = ";
-            builder.push(sql);
-            counter_builder.push(sql);
-            builder.push_bind(GDVersionEnum::All);
-            counter_builder.push_bind(GDVersionEnum::All);
-            let sql = ")";
-            builder.push(sql);
-            counter_builder.push(sql);
-        }
-
-        for (i, platform) in platforms.iter().enumerate() {
-            if i == 0 {
-                let sql = " AND mgv.platform IN (";
-                builder.push(sql);
-                counter_builder.push(sql);
-            }
-            builder.push_bind(*platform);
-            counter_builder.push_bind(*platform);
-            if i == platforms.len() - 1 {
-                builder.push(")");
-                counter_builder.push(")");
-            } else {
+            builder.push(
+                r#"row_number() over (partition by m.id order by mv.version_key desc nulls last, mv.id desc) rn FROM mods m
+                INNER JOIN mod_versions mv ON m.id = mv.mod_id
+                INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+                INNER JOIN mod_gd_versions mgv ON mgv.mod_id = mv.id "#
+            );
+            let mut counter_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+                "SELECT COUNT(DISTINCT m.id) FROM mods m
+                INNER JOIN mod_versions mv ON m.id = mv.mod_id
+                INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+                INNER JOIN mod_gd_versions mgv ON mgv.mod_id = mv.id ",
+            );
+
+            filters.push_where(&mut builder, None);
+            filters.push_where(&mut counter_builder, None);
+
+            builder.push(") q WHERE q.rn = 1");
+            if let Some(c) = &cursor {
+                builder.push(format!(
+                    " AND ({}, q.id) {} (",
+                    sort_columns.join(", "),
+                    if descending { "<" } else { ">" }
+                ));
+                match &c.key {
+                    ModIndexCursorKey::Downloads(d) => {
+                        builder.push_bind(*d);
+                    }
+                    ModIndexCursorKey::Timestamp(t) => {
+                        let t = DateTime::<Utc>::from_timestamp_micros(*t)
+                            .ok_or(ApiError::BadRequest("Invalid cursor".to_string()))?;
+                        builder.push_bind(t);
+                    }
+                    ModIndexCursorKey::Name(n) => {
+                        builder.push_bind(n.clone());
+                    }
+                    ModIndexCursorKey::Score(score, d) => {
+                        builder.push_bind(*score);
+                        builder.push(", ");
+                        builder.push_bind(*d);
+                    }
+                    ModIndexCursorKey::Trending(score, d) => {
+                        builder.push_bind(*score);
+                        builder.push(", ");
+                        builder.push_bind(*d);
+                    }
+                }
                 builder.push(", ");
-                counter_builder.push(", ");
+                builder.push_bind(c.id.clone());
+                builder.push(")");
             }
-        }
 
-        match query.sort {
-            IndexSortType::Downloads => {
-                builder.push(" ORDER BY m.download_count DESC");
-            }
-            IndexSortType::RecentlyUpdated => {
-                builder.push(" ORDER BY m.updated_at DESC");
-            }
-            IndexSortType::RecentlyPublished => {
-                builder.push(" ORDER BY m.created_at DESC");
-            }
-            IndexSortType::Name => {
-                builder.push(" ORDER BY mv.name ASC");
+            let order = if descending { " DESC" } else { " ASC" };
+            builder.push(" ORDER BY ");
+            for column in &sort_columns {
+                builder.push(column);
+                builder.push(order);
+                builder.push(", ");
             }
-            IndexSortType::NameReverse => {
-                builder.push(" ORDER BY mv.name DESC");
+            builder.push("q.id");
+            builder.push(order);
+            builder.push(" LIMIT ");
+            builder.push_bind(limit);
+            if cursor.is_none() {
+                builder.push(" OFFSET ");
+                builder.push_bind(offset);
             }
-        }
 
-        builder.push(") q WHERE q.rn = 1 LIMIT ");
-        builder.push_bind(limit);
-        builder.push(" OFFSET ");
-        builder.push_bind(offset);
+            let result = counter_builder
+                .build_query_scalar()
+                .fetch_one(&mut *pool)
+                .await;
+            let count = match result {
+                Err(e) => {
+                    log::error!("{}", e);
+                    return Err(ApiError::DbError);
+                }
+                Ok(c) => c,
+            };
 
-        let result = builder
-            .build_query_as::<ModRecord>()
-            .fetch_all(&mut *pool)
-            .await;
-        let records = match result {
-            Err(e) => {
-                log::error!("{}", e);
-                return Err(ApiError::DbError);
+            // Nothing matched the search as typed, retry with trigram similarity to catch typos
+            if count == 0 && filters.search.is_some() && !filters.fuzzy {
+                filters.fuzzy = true;
+                continue;
+            }
+
+            let result = builder
+                .build_query_as::<ModRecord>()
+                .fetch_all(&mut *pool)
+                .await;
+            match result {
+                Err(e) => {
+                    log::error!("{}", e);
+                    return Err(ApiError::DbError);
+                }
+                Ok(r) => break (r, count),
             }
-            Ok(r) => r,
         };
 
-        let result = counter_builder
-            .build_query_scalar()
-            .fetch_one(&mut *pool)
-            .await;
-        let count = match result {
-            Err(e) => {
-                log::error!("{}", e);
-                return Err(ApiError::DbError);
+        let facets = if facets.is_empty() {
+            None
+        } else {
+            let mut ret = HashMap::new();
+            for facet in facets {
+                ret.insert(facet, filters.get_facet_counts(facet, pool).await?);
             }
-            Ok(c) => c,
+            Some(ret)
         };
 
         if records.is_empty() {
-            return Ok(PaginatedData {
-                data: vec![],
-                count,
+            return Ok(ModIndex {
+                page: PaginatedData {
+                    data: vec![],
+                    count,
+                    next_cursor: None,
+                },
+                facets,
             });
         }
 
+        let next_cursor = match records.last() {
+            Some(last) if records.len() as i64 == limit => {
+                let key = match sort {
+                    IndexSortType::Downloads => ModIndexCursorKey::Downloads(last.download_count),
+                    IndexSortType::RecentlyUpdated => {
+                        ModIndexCursorKey::Timestamp(last.updated_at.timestamp_micros())
+                    }
+                    IndexSortType::RecentlyPublished => {
+                        ModIndexCursorKey::Timestamp(last.created_at.timestamp_micros())
+                    }
+                    IndexSortType::Name | IndexSortType::NameReverse => {
+                        ModIndexCursorKey::Name(last.name.clone())
+                    }
+                    IndexSortType::Relevance => {
+                        ModIndexCursorKey::Score(last.score, last.download_count)
+                    }
+                    IndexSortType::Trending => {
+                        ModIndexCursorKey::Trending(last.trending_score, last.download_count)
+                    }
+                };
+                Some(encode_cursor(&ModIndexCursor {
+                    key,
+                    id: last.id.clone(),
+                    fuzzy: filters.fuzzy,
+                }))
+            }
+            _ => None,
+        };
+
         if status == ModVersionStatusEnum::Pending {
-            return Mod::get_pending(records, count, pool).await;
+            let mut page = Mod::get_pending(records, count, pool).await?;
+            page.next_cursor = next_cursor;
+            return Ok(ModIndex { page, facets });
         }
 
         let ids: Vec<_> = records.iter().map(|x| x.id.clone()).collect();
-        let versions = ModVersion::get_latest_for_mods(pool, ids.clone(), query.gd, platforms, query.geode.as_ref()).await?;
+        let versions = ModVersion::get_latest_for_mods(pool, ids.clone(), query.gd, platforms, query.geode.as_ref(), query.channel).await?;
         let developers = Developer::fetch_for_mods(&ids, pool).await?;
         let mut mod_version_ids: Vec<i32> = vec![];
         for (_, mod_version) in versions.iter() {
@@ -405,6 +834,7 @@ impl Mod {
 
         let gd_versions = ModGDVersion::get_for_mod_versions(&mod_version_ids, pool).await?;
         let tags = Tag::get_tags_for_mods(&ids, pool).await?;
+        let deprecations = ModDeprecation::get_for_mods(&ids, pool).await?;
 
         let ret = records
             .into_iter()
@@ -427,10 +857,19 @@ impl Mod {
                     updated_at: x.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true),
                     about: None,
                     changelog: None,
+                    deprecation: deprecations.get(&x.id).cloned(),
+                    snippet: x.snippet,
                 }
             })
             .collect();
-        Ok(PaginatedData { data: ret, count })
+        Ok(ModIndex {
+            page: PaginatedData {
+                data: ret,
+                count,
+                next_cursor,
+            },
+            facets,
+        })
     }
 
     async fn get_pending(
@@ -448,6 +887,7 @@ impl Mod {
 
         let gd_versions = ModGDVersion::get_for_mod_versions(&mod_version_ids, pool).await?;
         let tags = Tag::get_tags_for_mods(&ids, pool).await?;
+        let deprecations = ModDeprecation::get_for_mods(&ids, pool).await?;
 
         let ret = records
             .into_iter()
@@ -470,6 +910,8 @@ impl Mod {
                     updated_at: x.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true),
                     about: x.about,
                     changelog: x.changelog,
+                    deprecation: deprecations.get(&x.id).cloned(),
+                    snippet: None,
                 }
             })
             .collect::<Vec<Mod>>();
@@ -477,6 +919,7 @@ impl Mod {
         Ok(PaginatedData {
             data: ret,
             count: total_count,
+            next_cursor: None,
         })
     }
 
@@ -570,12 +1013,14 @@ impl Mod {
             r#"SELECT
                 m.id, m.repository, m.about, m.changelog, m.featured, m.download_count as mod_download_count, m.created_at, m.updated_at,
                 mv.id as version_id, mv.name, mv.description, mv.version, mv.download_link, mv.download_count as mod_version_download_count,
-                mv.hash, mv.geode, mv.early_load, mv.api, mv.mod_id, mvs.status as "status: _"
+                mv.hash, mv.geode, mv.early_load, mv.api, mv.mod_id, mvs.status as "status: _",
+                mv.channel as "channel: _"
             FROM mods m
             INNER JOIN mod_versions mv ON m.id = mv.mod_id
             INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
             WHERE m.id = $1 
-            AND ($2 = false OR mvs.status = 'accepted')"#,
+            AND ($2 = false OR mvs.status = 'accepted')
+            ORDER BY mv.version_key DESC NULLS LAST, mv.id DESC"#,
             id,
             only_accepted
         )
@@ -599,6 +1044,7 @@ impl Mod {
                 early_load: x.early_load,
                 api: x.api,
                 status: x.status,
+                channel: x.channel,
                 mod_id: x.mod_id.clone(),
                 gd: DetailedGDVersion {
                     win: None,
@@ -620,6 +1066,7 @@ impl Mod {
         let gd = ModGDVersion::get_for_mod_versions(&ids, pool).await?;
         let tags = Tag::get_tags_for_mod(id, pool).await?;
         let devs = Developer::fetch_for_mod(id, pool).await?;
+        let deprecation = ModDeprecation::get_for_mod(id, pool).await?;
 
         for i in &mut versions {
             let gd_versions = gd.get(&i.id).cloned().unwrap_or_default();
@@ -642,10 +1089,111 @@ impl Mod {
                 .to_rfc3339_opts(SecondsFormat::Secs, true),
             about: records[0].about.clone(),
             changelog: records[0].changelog.clone(),
+            deprecation,
+            snippet: None,
         };
         Ok(Some(mod_entity))
     }
 
+    /**
+     * Same as get_one for several mods at once. Mods that aren't found are left out, the rest keep the order of ids.
+     */
+    pub async fn get_many(
+        ids: &Vec<String>,
+        only_accepted: bool,
+        pool: &mut PgConnection,
+    ) -> Result<Vec<Mod>, ApiError> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let records: Vec<ModRecordGetOne> = match sqlx::query_as!(
+            ModRecordGetOne,
+            r#"SELECT
+                m.id, m.repository, m.about, m.changelog, m.featured, m.download_count as mod_download_count, m.created_at, m.updated_at,
+                mv.id as version_id, mv.name, mv.description, mv.version, mv.download_link, mv.download_count as mod_version_download_count,
+                mv.hash, mv.geode, mv.early_load, mv.api, mv.mod_id, mvs.status as "status: _",
+                mv.channel as "channel: _"
+            FROM mods m
+            INNER JOIN mod_versions mv ON m.id = mv.mod_id
+            INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+            WHERE m.id = ANY($1)
+            AND ($2 = false OR mvs.status = 'accepted')
+            ORDER BY mv.version_key DESC NULLS LAST, mv.id DESC"#,
+            ids,
+            only_accepted
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        };
+        if records.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let version_ids: Vec<i32> = records.iter().map(|x| x.version_id).collect();
+        let found_ids: Vec<String> = ids
+            .iter()
+            .filter(|id| records.iter().any(|r| &r.id == *id))
+            .cloned()
+            .collect();
+        let gd = ModGDVersion::get_for_mod_versions(&version_ids, pool).await?;
+        let mut tags = Tag::get_tags_for_mods(&found_ids, pool).await?;
+        let mut devs = Developer::fetch_for_mods(&found_ids, pool).await?;
+        let mut deprecations = ModDeprecation::get_for_mods(&found_ids, pool).await?;
+
+        let mut versions: HashMap<String, Vec<ModVersion>> = HashMap::new();
+        for x in &records {
+            versions.entry(x.id.clone()).or_default().push(ModVersion {
+                id: x.version_id,
+                name: x.name.clone(),
+                description: x.description.clone(),
+                version: x.version.clone(),
+                download_link: x.download_link.clone(),
+                download_count: x.mod_version_download_count,
+                hash: x.hash.clone(),
+                geode: x.geode.clone(),
+                early_load: x.early_load,
+                api: x.api,
+                status: x.status,
+                channel: x.channel,
+                mod_id: x.mod_id.clone(),
+                gd: gd.get(&x.version_id).cloned().unwrap_or_default(),
+                developers: None,
+                tags: None,
+                dependencies: None,
+                incompatibilities: None,
+            });
+        }
+
+        let ret = found_ids
+            .into_iter()
+            .filter_map(|id| {
+                let record = records.iter().find(|r| r.id == id)?;
+                Some(Mod {
+                    id: record.id.clone(),
+                    repository: record.repository.clone(),
+                    featured: record.featured,
+                    download_count: record.mod_download_count,
+                    versions: versions.remove(&id).unwrap_or_default(),
+                    tags: tags.remove(&id).unwrap_or_default(),
+                    developers: devs.remove(&id).unwrap_or_default(),
+                    created_at: record.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    updated_at: record.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    about: record.about.clone(),
+                    changelog: record.changelog.clone(),
+                    deprecation: deprecations.remove(&id),
+                    snippet: None,
+                })
+            })
+            .collect();
+        Ok(ret)
+    }
+
     pub async fn from_json(
         json: &ModJson,
         developer: FetchedDeveloper,
@@ -668,6 +1216,7 @@ impl Mod {
 
         Mod::create(json, developer, pool).await?;
         ModVersion::create_from_json(json, dev_verified, pool).await?;
+        Mod::update_search_vector(&json.id, pool).await?;
         Ok(())
     }
 
@@ -697,7 +1246,7 @@ impl Mod {
             "SELECT mv.version, mv.id FROM mod_versions mv
             INNER JOIN mods m ON mv.mod_id = m.id
             WHERE m.id = $1
-            ORDER BY mv.id DESC LIMIT 1",
+            ORDER BY mv.version_key DESC NULLS LAST, mv.id DESC LIMIT 1",
             &json.id
         )
         .fetch_one(&mut *pool)
@@ -729,7 +1278,39 @@ impl Mod {
         ModVersion::create_from_json(json, developer.verified, pool).await?;
 
         Mod::update_existing_with_json(json, developer.verified, pool).await?;
+        Mod::update_search_vector(&json.id, pool).await?;
+
+        Ok(())
+    }
+
+    /**
+     * Replaces the package of a pending version in place. The new mod.json has to be for the
+     * same version, which is why this skips the version checks done by new_version.
+     */
+    pub async fn replace_pending_version(
+        json: &ModJson,
+        mod_version_id: i32,
+        developer: FetchedDeveloper,
+        pool: &mut PgConnection,
+    ) -> Result<(), ApiError> {
+        ModVersion::replace_from_json(mod_version_id, json, developer.id, pool).await?;
+        Mod::update_existing_with_json(json, false, pool).await?;
+        Mod::update_search_vector(&json.id, pool).await?;
+        Ok(())
+    }
 
+    /**
+     * Rebuilds the full text search document of a mod from its newest version,
+     * its about page and its developers
+     */
+    pub async fn update_search_vector(id: &str, pool: &mut PgConnection) -> Result<(), ApiError> {
+        if let Err(e) = sqlx::query!("SELECT update_mod_search_vector($1)", id)
+            .execute(&mut *pool)
+            .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
         Ok(())
     }
 
@@ -945,32 +1526,36 @@ impl Mod {
         update_timestamp: bool,
         pool: &mut PgConnection,
     ) -> Result<(), ApiError> {
-        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new("UPDATE mods SET ");
-        if json.repository.is_some() {
-            query_builder.push("repository = ");
-            query_builder.push_bind(&json.repository);
-            query_builder.push(", ");
-        }
-        if json.changelog.is_some() {
-            query_builder.push("changelog = ");
-            query_builder.push_bind(&json.changelog);
-            query_builder.push(", ");
-        }
-        if json.about.is_some() {
-            query_builder.push("about = ");
-            query_builder.push_bind(&json.about);
-        }
-        if !json.logo.is_empty() {
-            query_builder.push(", ");
-            query_builder.push("image = ");
-            query_builder.push_bind(&json.logo);
-        }
-        query_builder.push(" WHERE id = ");
-        query_builder.push_bind(&json.id);
+        if json.repository.is_some()
+            || json.changelog.is_some()
+            || json.about.is_some()
+            || !json.logo.is_empty()
+        {
+            let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new("UPDATE mods SET ");
+            let mut separated = query_builder.separated(", ");
+            if json.repository.is_some() {
+                separated.push("repository = ");
+                separated.push_bind_unseparated(&json.repository);
+            }
+            if json.changelog.is_some() {
+                separated.push("changelog = ");
+                separated.push_bind_unseparated(&json.changelog);
+            }
+            if json.about.is_some() {
+                separated.push("about = ");
+                separated.push_bind_unseparated(&json.about);
+            }
+            if !json.logo.is_empty() {
+                separated.push("image = ");
+                separated.push_bind_unseparated(&json.logo);
+            }
+            query_builder.push(" WHERE id = ");
+            query_builder.push_bind(&json.id);
 
-        if let Err(e) = query_builder.build().execute(&mut *pool).await {
-            log::error!("{}", e);
-            return Err(ApiError::DbError);
+            if let Err(e) = query_builder.build().execute(&mut *pool).await {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
         }
 
         if update_timestamp {
@@ -997,19 +1582,41 @@ impl Mod {
         Ok(())
     }
 
-    pub async fn calculate_cached_downloads(
-        mod_id: &str,
+    /**
+     * Adds new downloads onto the cached counts of their mods. A mod counts an address once per
+     * dedup window over all of its versions, even if the versions counted it separately.
+     */
+    pub async fn add_cached_downloads(
+        download_ids: &[i64],
+        dedup_window_hours: i32,
         pool: &mut PgConnection,
     ) -> Result<(), ApiError> {
         if let Err(e) = sqlx::query!(
-            "UPDATE mods m SET download_count = (
-                SELECT COUNT(DISTINCT md.ip) FROM mod_downloads md
+            "UPDATE mods m
+            SET download_count = m.download_count + c.downloads,
+            last_download_cache_refresh = GREATEST(m.last_download_cache_refresh, now())
+            FROM (
+                SELECT mv.mod_id, COUNT(*) AS downloads FROM mod_downloads md
                 INNER JOIN mod_versions mv ON md.mod_version_id = mv.id
                 INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
-                WHERE mv.mod_id = m.id AND mvs.status = 'accepted'
-            ), last_download_cache_refresh = now()
-            WHERE m.id = $1", mod_id
-        ).execute(&mut *pool).await {
+                WHERE md.id = ANY($1) AND mvs.status = 'accepted'
+                AND NOT EXISTS (
+                    SELECT 1 FROM mod_downloads prev
+                    INNER JOIN mod_versions pmv ON prev.mod_version_id = pmv.id
+                    INNER JOIN mod_version_statuses pmvs ON pmvs.mod_version_id = pmv.id
+                    WHERE pmv.mod_id = mv.mod_id AND pmvs.status = 'accepted' AND prev.ip_hash = md.ip_hash
+                    AND prev.id < md.id
+                    AND prev.time_downloaded > md.time_downloaded - make_interval(hours => $2)
+                )
+                GROUP BY mv.mod_id
+            ) c
+            WHERE m.id = c.mod_id",
+            download_ids,
+            dedup_window_hours
+        )
+        .execute(&mut *pool)
+        .await
+        {
             log::error!("{}", e);
             return Err(ApiError::DbError);
         }
@@ -1115,7 +1722,7 @@ impl Mod {
             )));
         }
 
-        match sqlx::query!(
+        if let Err(err) = sqlx::query!(
             "INSERT INTO mods_developers (mod_id, developer_id)
             VALUES ($1, $2)",
             mod_id,
@@ -1124,12 +1731,11 @@ impl Mod {
         .execute(&mut *pool)
         .await
         {
-            Err(err) => {
-                log::error!("{}", err);
-                Err(ApiError::DbError)
-            }
-            Ok(_) => Ok(()),
+            log::error!("{}", err);
+            return Err(ApiError::DbError);
         }
+
+        Mod::update_search_vector(mod_id, pool).await
     }
 
     pub async fn unassign_dev(
@@ -1168,7 +1774,7 @@ impl Mod {
             ));
         }
 
-        match sqlx::query!(
+        if let Err(e) = sqlx::query!(
             "DELETE FROM mods_developers
             WHERE mod_id = $1 AND developer_id = $2",
             mod_id,
@@ -1177,19 +1783,19 @@ impl Mod {
         .execute(&mut *pool)
         .await
         {
-            Err(e) => {
-                log::error!("{}", e);
-                Err(ApiError::DbError)
-            }
-            Ok(_) => Ok(()),
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
         }
+
+        Mod::update_search_vector(mod_id, pool).await
     }
 
     pub async fn get_updates(
         ids: &[String],
         platforms: VerPlatform,
         geode: &semver::Version,
-        gd: GDVersionEnum,
+        gd: &str,
+        channel: ModVersionChannel,
         pool: &mut PgConnection,
     ) -> Result<Vec<ModUpdate>, ApiError> {
         let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
@@ -1201,7 +1807,7 @@ impl Mod {
                 SELECT m.id, 
                     mv.id as mod_version_id,
                     mv.version as inner_version,
-                    row_number() over (partition by m.id order by mv.id desc) rn 
+                    row_number() over (partition by m.id order by mv.version_key desc nulls last, mv.id desc) rn 
                 FROM mods m
                 INNER JOIN mod_versions mv ON mv.mod_id = m.id 
                 INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
@@ -1214,33 +1820,14 @@ impl Mod {
         builder.push_bind(gd);
         builder.push(" OR mgv.gd = '*')");
 
+        builder.push(" AND mv.channel <= ");
+        builder.push_bind(channel);
+
         builder.push(" AND m.id = ANY(");
         builder.push_bind(ids);
         builder.push(") ");
 
-        if geode.pre.contains("alpha") {
-            builder.push(" AND mv.geode = ");
-            builder.push_bind(geode.to_string());
-        } else {
-            let sql = " AND (SPLIT_PART(mv.geode, '.', 1) = ";
-            builder.push(sql);
-            builder.push_bind(geode.major.to_string());
-
-            let sql = " AND SPLIT_PART(mv.geode, '-', 2) NOT LIKE 'alpha%' AND SPLIT_PART(mv.geode, '.', 2) <= ";
-            builder.push(sql);
-            builder.push_bind(geode.minor.to_string());
-
-            // Match only higher betas (or no beta)
-            if geode.pre.contains("beta") {
-                let sql = " AND (SPLIT_PART(mv.geode, '-', 2) = ''
-                    OR SPLIT_PART(mv.geode, '-', 2) <=";
-                builder.push(sql);
-                builder.push_bind(geode.pre.to_string());
-                builder.push(")");
-            }
-
-            builder.push(")");
-        }
+        push_compatible_filter(&mut builder, "mv.geode", geode);
 
         builder.push(") q where q.rn = 1");
 
@@ -1280,9 +1867,9 @@ impl Mod {
         for r in result {
             let update = ModUpdate {
                 id: r.id.clone(),
-                version: r.version,
+                version: Some(r.version),
                 mod_version_id: r.mod_version_id,
-                download_link: "".to_string(),
+                download_link: None,
                 dependencies: vec![],
                 incompatibilities: vec![],
                 // dependencies: deps
@@ -1299,7 +1886,8 @@ impl Mod {
                 //     .iter()
                 //     .map(|x| x.to_response())
                 //     .collect(),
-                replacement: None
+                replacement: None,
+                deprecation: None,
             };
             ret.push(update);
         }
@@ -8,43 +8,7 @@ use sqlx::{PgConnection, Postgres, QueryBuilder};
 
 use crate::types::{api::ApiError, mod_json::ModJson};
 
-#[derive(sqlx::Type, Debug, Deserialize, Serialize, Clone, Copy)]
-#[sqlx(type_name = "gd_version")]
-pub enum GDVersionEnum {
-    #[serde(rename = "*")]
-    #[sqlx(rename = "*")]
-    All,
-    #[serde(rename = "2.113")]
-    #[sqlx(rename = "2.113")]
-    GD2113,
-    #[serde(rename = "2.200")]
-    #[sqlx(rename = "2.200")]
-    GD2200,
-    #[serde(rename = "2.204")]
-    #[sqlx(rename = "2.204")]
-    GD2204,
-    #[serde(rename = "2.205")]
-    #[sqlx(rename = "2.205")]
-    GD2205,
-    #[serde(rename = "2.206")]
-    #[sqlx(rename = "2.206")]
-    GD2206,
-}
-
-impl FromStr for GDVersionEnum {
-    type Err = ();
-    fn from_str(s: &str) -> Result<Self, ()> {
-        match s {
-            "*" => Ok(GDVersionEnum::All),
-            "2.113" => Ok(GDVersionEnum::GD2113),
-            "2.200" => Ok(GDVersionEnum::GD2200),
-            "2.204" => Ok(GDVersionEnum::GD2204),
-            "2.205" => Ok(GDVersionEnum::GD2205),
-            "2.206" => Ok(GDVersionEnum::GD2206),
-            _ => Err(()),
-        }
-    }
-}
+use super::gd_version::GDVersion;
 
 #[derive(sqlx::Type, Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[sqlx(type_name = "gd_ver_platform", rename_all = "lowercase")]
@@ -86,6 +50,39 @@ impl FromStr for VerPlatform {
 }
 
 impl VerPlatform {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerPlatform::Android => "android",
+            VerPlatform::Android32 => "android32",
+            VerPlatform::Android64 => "android64",
+            VerPlatform::Ios => "ios",
+            VerPlatform::Mac => "mac",
+            VerPlatform::MacArm => "mac-arm",
+            VerPlatform::MacIntel => "mac-intel",
+            VerPlatform::Win => "win",
+        }
+    }
+
+    /**
+     * Replaces android and mac with their concrete architectures, dropping duplicates
+     */
+    pub fn expand(platforms: &[VerPlatform]) -> Vec<VerPlatform> {
+        let mut ret: Vec<VerPlatform> = vec![];
+        for i in platforms {
+            let expanded = match i {
+                VerPlatform::Android => vec![VerPlatform::Android32, VerPlatform::Android64],
+                VerPlatform::Mac => vec![VerPlatform::MacArm, VerPlatform::MacIntel],
+                p => vec![*p],
+            };
+            for p in expanded {
+                if !ret.contains(&p) {
+                    ret.push(p);
+                }
+            }
+        }
+        ret
+    }
+
     pub fn parse_query_string(s: &str) -> Vec<VerPlatform> {
         let mut ret = vec![];
         if s.is_empty() {
@@ -111,79 +108,100 @@ impl VerPlatform {
     }
 }
 
-#[derive(sqlx::FromRow, Clone, Copy, Debug, Serialize)]
+#[derive(sqlx::FromRow, Clone, Debug, Serialize)]
 pub struct ModGDVersion {
     id: i32,
     mod_id: i32,
-    gd: GDVersionEnum,
+    gd: String,
     platform: VerPlatform,
 }
 
 pub struct ModGDVersionCreate {
-    pub gd: GDVersionEnum,
+    pub gd: String,
     pub platform: VerPlatform,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct DetailedGDVersion {
-    pub win: Option<GDVersionEnum>,
+    pub win: Option<String>,
     #[serde(skip_serializing)]
-    pub android: Option<GDVersionEnum>,
+    pub android: Option<String>,
     #[serde(skip_deserializing)]
-    pub android32: Option<GDVersionEnum>,
+    pub android32: Option<String>,
     #[serde(skip_deserializing)]
-    pub android64: Option<GDVersionEnum>,
+    pub android64: Option<String>,
     #[serde(skip_serializing)]
-    pub mac: Option<GDVersionEnum>,
+    pub mac: Option<String>,
     #[serde(rename = "mac-intel")]
-    pub mac_intel: Option<GDVersionEnum>,
+    pub mac_intel: Option<String>,
     #[serde(rename = "mac-arm")]
-    pub mac_arm: Option<GDVersionEnum>,
-    pub ios: Option<GDVersionEnum>,
+    pub mac_arm: Option<String>,
+    pub ios: Option<String>,
 }
 
 impl DetailedGDVersion {
+    /**
+     * Lists every supported platform with its gd version, android and mac are already split by architecture
+     */
+    pub fn platforms(&self) -> Vec<(VerPlatform, String)> {
+        [
+            (VerPlatform::Win, &self.win),
+            (VerPlatform::Android32, &self.android32),
+            (VerPlatform::Android64, &self.android64),
+            (VerPlatform::MacArm, &self.mac_arm),
+            (VerPlatform::MacIntel, &self.mac_intel),
+            (VerPlatform::Ios, &self.ios),
+        ]
+        .into_iter()
+        .filter_map(|(platform, gd)| gd.clone().map(|gd| (platform, gd)))
+        .collect()
+    }
+
     pub fn to_create_payload(&self, json: &ModJson) -> Vec<ModGDVersionCreate> {
         let mut ret: Vec<_> = vec![];
-        if self.android.is_some() {
+        if let Some(gd) = &self.android {
             if json.android32 {
                 ret.push(ModGDVersionCreate {
-                    gd: self.android.unwrap(),
+                    gd: gd.clone(),
                     platform: VerPlatform::Android32,
                 });
             }
             if json.android64 {
                 ret.push(ModGDVersionCreate {
-                    gd: self.android.unwrap(),
+                    gd: gd.clone(),
                     platform: VerPlatform::Android64,
                 })
             }
         }
-        if self.win.is_some() && json.windows {
-            ret.push(ModGDVersionCreate {
-                gd: self.win.unwrap(),
-                platform: VerPlatform::Win,
-            });
+        if let Some(gd) = &self.win {
+            if json.windows {
+                ret.push(ModGDVersionCreate {
+                    gd: gd.clone(),
+                    platform: VerPlatform::Win,
+                });
+            }
         }
-        if self.mac.is_some() {
+        if let Some(gd) = &self.mac {
             if json.mac_arm {
                 ret.push(ModGDVersionCreate {
-                    gd: self.mac.unwrap(),
+                    gd: gd.clone(),
                     platform: VerPlatform::MacArm,
                 })
             }
             if json.mac_intel {
                 ret.push(ModGDVersionCreate {
-                    gd: self.mac.unwrap(),
+                    gd: gd.clone(),
                     platform: VerPlatform::MacIntel,
                 })
             }
         }
-        if self.ios.is_some() && json.ios {
-            ret.push(ModGDVersionCreate {
-                gd: self.ios.unwrap(),
-                platform: VerPlatform::Ios,
-            });
+        if let Some(gd) = &self.ios {
+            if json.ios {
+                ret.push(ModGDVersionCreate {
+                    gd: gd.clone(),
+                    platform: VerPlatform::Ios,
+                });
+            }
         }
 
         ret
@@ -206,13 +224,15 @@ impl ModGDVersion {
             return Err(ApiError::BadRequest(e));
         }
 
+        GDVersion::validate_for_platforms(&json, &mut *pool).await?;
+
         let mut builder: QueryBuilder<Postgres> =
             QueryBuilder::new("INSERT INTO mod_gd_versions (gd, platform, mod_id) VALUES ");
         let mut i = 0;
         for current in json.iter() {
             builder.push("(");
             let mut separated = builder.separated(", ");
-            separated.push_bind(current.gd as GDVersionEnum);
+            separated.push_bind(current.gd.clone());
             separated.push_bind(current.platform as VerPlatform);
             separated.push_bind(mod_version_id);
             separated.push_unseparated(")");
@@ -235,7 +255,7 @@ impl ModGDVersion {
         id: i32,
         pool: &mut PgConnection,
     ) -> Result<DetailedGDVersion, ApiError> {
-        let result = sqlx::query_as!(ModGDVersion, r#"SELECT mgv.id, mgv.mod_id, mgv.gd AS "gd: _", mgv.platform as "platform: _" FROM mod_gd_versions mgv WHERE mgv.mod_id = $1"#, id)
+        let result = sqlx::query_as!(ModGDVersion, r#"SELECT mgv.id, mgv.mod_id, mgv.gd, mgv.platform as "platform: _" FROM mod_gd_versions mgv WHERE mgv.mod_id = $1"#, id)
             .fetch_all(&mut *pool)
             .await;
         let result: Vec<ModGDVersion> = match result {
@@ -260,14 +280,14 @@ impl ModGDVersion {
                 VerPlatform::Android32 => ret.android32 = Some(i.gd),
                 VerPlatform::Android64 => ret.android64 = Some(i.gd),
                 VerPlatform::Android => {
-                    ret.android32 = Some(i.gd);
+                    ret.android32 = Some(i.gd.clone());
                     ret.android64 = Some(i.gd);
                 }
                 VerPlatform::MacArm => ret.mac_arm = Some(i.gd),
                 VerPlatform::MacIntel => ret.mac_intel = Some(i.gd),
                 VerPlatform::Win => ret.win = Some(i.gd),
                 VerPlatform::Mac => {
-                    ret.mac_arm = Some(i.gd);
+                    ret.mac_arm = Some(i.gd.clone());
                     ret.mac_intel = Some(i.gd);
                 }
                 VerPlatform::Ios => ret.ios = Some(i.gd),
@@ -312,7 +332,7 @@ impl ModGDVersion {
                     let mut ver = DetailedGDVersion::default();
                     match i.platform {
                         VerPlatform::Android => {
-                            ver.android32 = Some(i.gd);
+                            ver.android32 = Some(i.gd.clone());
                             ver.android64 = Some(i.gd);
                         }
                         VerPlatform::Android32 => ver.android32 = Some(i.gd),
@@ -320,7 +340,7 @@ impl ModGDVersion {
                         VerPlatform::MacArm => ver.mac_arm = Some(i.gd),
                         VerPlatform::MacIntel => ver.mac_intel = Some(i.gd),
                         VerPlatform::Mac => {
-                            ver.mac_arm = Some(i.gd);
+                            ver.mac_arm = Some(i.gd.clone());
                             ver.mac_intel = Some(i.gd);
                         }
                         VerPlatform::Ios => ver.ios = Some(i.gd),
@@ -330,13 +350,13 @@ impl ModGDVersion {
                 }
                 Entry::Occupied(mut e) => match i.platform {
                     VerPlatform::Android => {
-                        e.get_mut().android32 = Some(i.gd);
+                        e.get_mut().android32 = Some(i.gd.clone());
                         e.get_mut().android64 = Some(i.gd);
                     }
                     VerPlatform::Android32 => e.get_mut().android32 = Some(i.gd),
                     VerPlatform::Android64 => e.get_mut().android64 = Some(i.gd),
                     VerPlatform::Mac => {
-                        e.get_mut().mac_arm = Some(i.gd);
+                        e.get_mut().mac_arm = Some(i.gd.clone());
                         e.get_mut().mac_intel = Some(i.gd);
                     }
                     VerPlatform::MacArm => e.get_mut().mac_arm = Some(i.gd),
@@ -352,11 +372,11 @@ impl ModGDVersion {
 }
 
 fn check_for_duplicate_platforms(versions: &Vec<ModGDVersionCreate>) -> Result<(), String> {
-    let mut found: HashMap<VerPlatform, GDVersionEnum> = HashMap::new();
+    let mut found: HashMap<VerPlatform, &str> = HashMap::new();
     for i in versions {
         match found.get(&i.platform) {
             Some(_) => return Err("Duplicated platforms detected in mod.json gd key".to_string()),
-            None => found.insert(i.platform, i.gd),
+            None => found.insert(i.platform, &i.gd),
         };
     }
     Ok(())
@@ -1,12 +1,16 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display};
 
 use chrono::Utc;
 use semver::Version;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{PgConnection, Postgres, QueryBuilder, Row};
 
 use crate::types::{
-    api::{create_download_link, ApiError, PaginatedData},
+    api::{
+        create_download_link, create_platform_download_link, decode_cursor, encode_cursor,
+        ApiError, PaginatedData,
+    },
+    geode_compat::{parse_loader_version, push_compatible_filter},
     mod_json::ModJson,
     models::mod_entity::Mod,
 };
@@ -15,8 +19,9 @@ use super::{
     dependency::{Dependency, ModVersionCompare, ResponseDependency},
     developer::Developer,
     incompatibility::{Incompatibility, ResponseIncompatibility},
-    mod_gd_version::{DetailedGDVersion, GDVersionEnum, ModGDVersion, VerPlatform},
-    mod_version_status::{ModVersionStatus, ModVersionStatusEnum},
+    mod_gd_version::{DetailedGDVersion, ModGDVersion, VerPlatform},
+    mod_version_artifact::ModVersionArtifact,
+    mod_version_status::{ModVersionStatus, ModVersionStatusEnum, ModVersionStatusEvent},
     tag::Tag,
 };
 
@@ -36,6 +41,7 @@ pub struct ModVersion {
     pub mod_id: String,
     pub gd: DetailedGDVersion,
     pub status: ModVersionStatusEnum,
+    pub channel: ModVersionChannel,
     pub dependencies: Option<Vec<ResponseDependency>>,
     pub incompatibilities: Option<Vec<ResponseIncompatibility>>,
     pub developers: Option<Vec<Developer>>,
@@ -56,16 +62,81 @@ struct ModVersionGetOne {
     api: bool,
     mod_id: String,
     status: ModVersionStatusEnum,
+    channel: ModVersionChannel,
+}
+
+/// Release channels are ordered from most to least stable, so a channel
+/// also accepts every version from the channels before it
+#[derive(
+    sqlx::Type, Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[sqlx(type_name = "mod_version_channel", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ModVersionChannel {
+    #[default]
+    Stable,
+    Beta,
+    Alpha,
+}
+
+impl ModVersionChannel {
+    pub fn from_version(version: &Version) -> ModVersionChannel {
+        if version.pre.is_empty() {
+            ModVersionChannel::Stable
+        } else if version.pre.contains("alpha") {
+            ModVersionChannel::Alpha
+        } else {
+            ModVersionChannel::Beta
+        }
+    }
+
+    /**
+     * Picks the channel for a new version. An explicit channel can only make a version less stable than its semver says
+     */
+    pub fn resolve(
+        version: &Version,
+        explicit: Option<ModVersionChannel>,
+    ) -> Result<ModVersionChannel, ApiError> {
+        let derived = ModVersionChannel::from_version(version);
+        match explicit {
+            None => Ok(derived),
+            Some(c) if c >= derived => Ok(c),
+            Some(c) => Err(ApiError::BadRequest(format!(
+                "Version {} can't be released on the {} channel",
+                version, c
+            ))),
+        }
+    }
+}
+
+impl Display for ModVersionChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stable => write!(f, "stable"),
+            Self::Beta => write!(f, "beta"),
+            Self::Alpha => write!(f, "alpha"),
+        }
+    }
 }
 
 pub struct IndexQuery {
     pub mod_id: String,
     pub page: i64,
     pub per_page: i64,
-    pub gd: Option<GDVersionEnum>,
+    pub gd: Option<String>,
     pub compare: Option<(semver::Version, ModVersionCompare)>,
     pub platforms: Vec<VerPlatform>,
     pub status: ModVersionStatusEnum,
+    pub cursor: Option<String>,
+}
+
+/**
+ * Position of the last version of an index page
+ */
+#[derive(Serialize, Deserialize)]
+struct ModVersionIndexCursor {
+    version: String,
+    id: i32,
 }
 
 impl ModVersionGetOne {
@@ -83,6 +154,7 @@ impl ModVersionGetOne {
             api: self.api,
             mod_id: self.mod_id.clone(),
             status: self.status,
+            channel: self.channel,
             gd: DetailedGDVersion {
                 win: None,
                 android: None,
@@ -106,18 +178,31 @@ impl ModVersion {
         self.download_link = create_download_link(app_url, &self.mod_id, &self.version)
     }
 
+    /**
+     * Points the version to its package for a platform instead of the main one
+     */
+    pub fn use_artifact(&mut self, artifact: ModVersionArtifact, app_url: &str) {
+        self.hash = artifact.hash;
+        self.download_link =
+            create_platform_download_link(app_url, &self.mod_id, &self.version, artifact.platform);
+    }
+
     pub async fn get_index(
         query: IndexQuery,
         pool: &mut PgConnection,
     ) -> Result<PaginatedData<ModVersion>, ApiError> {
         let limit = query.per_page;
         let offset = (query.page - 1) * query.per_page;
+        let cursor: Option<ModVersionIndexCursor> = match &query.cursor {
+            Some(c) => Some(decode_cursor(c)?),
+            None => None,
+        };
 
         let mut q: QueryBuilder<Postgres> = QueryBuilder::new(
             r#"
             SELECT mv.id, mv.name, mv.description, mv.version,
             mv.download_link, mv.download_count, mv.hash, mv.geode,
-            mv.early_load, mv.api, mv.mod_id, mvs.status
+            mv.early_load, mv.api, mv.mod_id, mvs.status, mv.channel
             FROM mod_versions mv
             INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
             INNER JOIN mod_gd_versions mgv ON mgv.mod_id = mv.id
@@ -147,15 +232,11 @@ impl ModVersion {
             let sql = "AND (mgv.gd = ";
             q.push(sql);
             counter_q.push(sql);
-            q.push_bind(gd);
+            q.push_bind(gd.clone());
             counter_q.push_bind(gd);
-            let sql = " OR mgv.gd = ";
+            let sql = " OR mgv.gd = '*') ";
             q.push(sql);
             counter_q.push(sql);
-            q.push_bind(GDVersionEnum::All);
-            counter_q.push_bind(GDVersionEnum::All);
-            q.push(" ");
-            counter_q.push(" ");
         }
         if !query.platforms.is_empty() {
             let sql = "AND mgv.platform IN (";
@@ -194,12 +275,28 @@ impl ModVersion {
             counter_q.push(sql);
         }
 
-        let sql = "GROUP BY mv.id, mvs.status ORDER BY mv.id DESC LIMIT ";
+        if let Some(c) = &cursor {
+            // Keyset on (version_key DESC NULLS LAST, id DESC), versions that don't parse have no key
+            q.push("AND ((semver_sort_key(");
+            q.push_bind(c.version.clone());
+            q.push(") IS NOT NULL AND (mv.version_key < semver_sort_key(");
+            q.push_bind(c.version.clone());
+            q.push(") OR mv.version_key IS NULL)) OR (mv.version_key IS NOT DISTINCT FROM semver_sort_key(");
+            q.push_bind(c.version.clone());
+            q.push(") AND mv.id < ");
+            q.push_bind(c.id);
+            q.push(")) ");
+        }
+
+        let sql =
+            "GROUP BY mv.id, mvs.status ORDER BY mv.version_key DESC NULLS LAST, mv.id DESC LIMIT ";
         q.push(sql);
         q.push_bind(limit);
-        let sql = " OFFSET ";
-        q.push(sql);
-        q.push_bind(offset);
+        if cursor.is_none() {
+            let sql = " OFFSET ";
+            q.push(sql);
+            q.push_bind(offset);
+        }
 
         let records = match q
             .build_query_as::<ModVersionGetOne>()
@@ -225,11 +322,30 @@ impl ModVersion {
             return Ok(PaginatedData {
                 data: vec![],
                 count,
+                next_cursor: None,
             });
         }
 
+        let next_cursor = match records.last() {
+            Some(last) if records.len() as i64 == limit => {
+                Some(encode_cursor(&ModVersionIndexCursor {
+                    version: last.version.clone(),
+                    id: last.id,
+                }))
+            }
+            _ => None,
+        };
+
         let version_ids: Vec<i32> = records.iter().map(|x| x.id).collect();
-        let deps = Dependency::get_for_mod_versions(&version_ids, None, None, None, pool).await?;
+        let deps = Dependency::get_for_mod_versions(
+            &version_ids,
+            None,
+            None,
+            None,
+            ModVersionChannel::Stable,
+            pool,
+        )
+        .await?;
         let incompat =
             Incompatibility::get_for_mod_versions(&version_ids, None, None, None, pool).await?;
 
@@ -260,15 +376,20 @@ impl ModVersion {
             })
             .collect();
 
-        Ok(PaginatedData { data: ret, count })
+        Ok(PaginatedData {
+            data: ret,
+            count,
+            next_cursor,
+        })
     }
 
     pub async fn get_latest_for_mods(
         pool: &mut PgConnection,
         ids: Vec<String>,
-        gd: Option<GDVersionEnum>,
+        gd: Option<String>,
         platforms: Vec<VerPlatform>,
         geode: Option<&String>,
+        channel: ModVersionChannel,
     ) -> Result<HashMap<String, ModVersion>, ApiError> {
         if ids.is_empty() {
             return Ok(Default::default());
@@ -276,53 +397,26 @@ impl ModVersion {
 
         let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
             r#"SELECT q.name, q.id, q.description, q.version, q.download_link, q.hash, q.geode, q.download_count,
-                q.early_load, q.api, q.mod_id, q.status FROM (
+                q.early_load, q.api, q.mod_id, q.status, q.channel FROM (
                     SELECT
                     mv.name, mv.id, mv.description, mv.version, mv.download_link, mv.hash, mv.geode, mv.download_count, mvs.status,
-                    mv.early_load, mv.api, mv.mod_id, row_number() over (partition by m.id order by mv.id desc) rn FROM mods m 
+                    mv.channel, mv.early_load, mv.api, mv.mod_id, row_number() over (partition by m.id order by mv.version_key desc nulls last, mv.id desc) rn FROM mods m 
                     INNER JOIN mod_versions mv ON m.id = mv.mod_id
                     INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
                     INNER JOIN mod_gd_versions mgv ON mgv.mod_id = mv.id
                     WHERE mvs.status = 'accepted' 
             "#,
         );
+        builder.push(" AND mv.channel <= ");
+        builder.push_bind(channel);
         if let Some(g) = gd {
             builder.push(" AND (mgv.gd = ");
             builder.push_bind(g);
-            builder.push(" OR mgv.gd = ");
-            builder.push_bind(GDVersionEnum::All);
-            builder.push(")");
-        }
-
-        if let Some(geode) = geode {
-            let geode = geode.trim_start_matches('v').to_string();
-            if let Ok(parsed) = Version::parse(&geode) {
-                // If alpha, match exactly that version
-                if parsed.pre.contains("alpha") {
-                    let sql = " AND mv.geode = ";
-                    builder.push(sql);
-                    builder.push_bind(parsed.to_string());
-                } else {
-                    let sql = " AND (SPLIT_PART(mv.geode, '.', 1) = ";
-                    builder.push(sql);
-                    builder.push_bind(parsed.major.to_string());
-
-                    let sql = " AND SPLIT_PART(mv.geode, '-', 2) NOT LIKE 'alpha%' AND SPLIT_PART(mv.geode, '.', 2) <= ";
-                    builder.push(sql);
-                    builder.push_bind(parsed.minor.to_string());
-
-                    // Match only higher betas (or no beta)
-                    if parsed.pre.contains("beta") {
-                        let sql = " AND (SPLIT_PART(mv.geode, '-', 2) = ''
-                            OR SPLIT_PART(mv.geode, '-', 2) <=";
-                        builder.push(sql);
-                        builder.push_bind(parsed.pre.to_string());
-                        builder.push(")");
-                    }
+            builder.push(" OR mgv.gd = '*')");
+        }
 
-                    builder.push(")");
-                }
-            }
+        if let Some(geode) = geode.and_then(|x| parse_loader_version(x)) {
+            push_compatible_filter(&mut builder, "mv.geode", &geode);
         }
 
         for (i, platform) in platforms.iter().enumerate() {
@@ -365,6 +459,85 @@ impl ModVersion {
         Ok(ret)
     }
 
+    /**
+     * Picks the newest accepted version of every mod in a collection that matches the
+     * collection's version requirement, is on the channel and runs on the given platform, gd and loader
+     */
+    pub async fn get_for_collection(
+        collection_id: i32,
+        platform: VerPlatform,
+        gd: &str,
+        geode: &Version,
+        channel: ModVersionChannel,
+        pool: &mut PgConnection,
+    ) -> Result<HashMap<String, ModVersion>, ApiError> {
+        let records = match sqlx::query_as::<Postgres, ModVersionGetOne>(
+            r#"SELECT q.name, q.id, q.description, q.version, q.download_link, q.hash, q.geode, q.download_count,
+                q.early_load, q.api, q.mod_id, q.status, q.channel FROM (
+                    SELECT mv.name, mv.id, mv.description, mv.version, mv.download_link, mv.hash, mv.geode,
+                        mv.download_count, mv.early_load, mv.api, mv.mod_id, mvs.status, mv.channel,
+                        ROW_NUMBER() OVER(
+                            PARTITION BY cm.mod_id
+                            ORDER BY mv.version_key DESC NULLS LAST, mv.id DESC
+                        ) rn
+                    FROM collection_mods cm
+                    INNER JOIN mod_versions mv ON mv.mod_id = cm.mod_id
+                    INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
+                    WHERE cm.collection_id = $1
+                    AND mvs.status = 'accepted'
+                    AND EXISTS (
+                        SELECT 1 FROM mod_gd_versions mgv
+                        WHERE mgv.mod_id = mv.id
+                        AND mgv.platform = $2
+                        AND (mgv.gd = $3 OR mgv.gd = '*')
+                    )
+                    AND geode_compatible(mv.geode, $4)
+                    AND mv.channel <= $5
+                    AND CASE
+                        WHEN cm.version IS NULL THEN true
+                        WHEN cm.compare = '<' THEN semver_compare(mv.version, cm.version) = -1
+                        WHEN cm.compare = '>' THEN semver_compare(mv.version, cm.version) = 1
+                        WHEN cm.compare = '<=' THEN semver_compare(mv.version, cm.version) <= 0
+                        WHEN cm.compare = '>=' THEN semver_compare(mv.version, cm.version) >= 0
+                        WHEN cm.compare = '=' THEN semver_compare(mv.version, cm.version) = 0
+                        ELSE false
+                    END
+                ) q
+            WHERE q.rn = 1"#,
+        )
+        .bind(collection_id)
+        .bind(platform)
+        .bind(gd)
+        .bind(geode.to_string())
+        .bind(channel)
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+
+        if records.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let ids: Vec<i32> = records.iter().map(|x| x.id).collect();
+        let mut gd_versions = ModGDVersion::get_for_mod_versions(&ids, pool).await?;
+
+        Ok(records
+            .into_iter()
+            .map(|x| {
+                let mut version = x.into_mod_version();
+                if let Some(gd) = gd_versions.remove(&version.id) {
+                    version.gd = gd;
+                }
+                (version.mod_id.clone(), version)
+            })
+            .collect())
+    }
+
     pub async fn get_pending_for_mods(
         ids: &Vec<String>,
         pool: &mut PgConnection,
@@ -376,7 +549,7 @@ impl ModVersion {
         let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
             r#"SELECT DISTINCT
             mv.name, mv.id, mv.description, mv.version, mv.download_link, mv.hash, mv.geode, mv.download_count,
-            mv.early_load, mv.api, mv.mod_id, mvs.status FROM mod_versions mv 
+            mv.early_load, mv.api, mv.mod_id, mvs.status, mv.channel FROM mod_versions mv 
             INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
             WHERE mvs.status = 'pending' AND mv.mod_id IN ("#,
         );
@@ -411,26 +584,29 @@ impl ModVersion {
 
     pub async fn get_latest_for_mod(
         id: &str,
-        gd: Option<GDVersionEnum>,
+        gd: Option<String>,
         platforms: Vec<VerPlatform>,
         major: Option<u32>,
+        channel: ModVersionChannel,
         pool: &mut PgConnection,
     ) -> Result<ModVersion, ApiError> {
         let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
             r#"SELECT q.name, q.id, q.description, q.version, q.download_link, 
                 q.hash, q.geode, q.download_count,
-                q.early_load, q.api, q.mod_id, q.status 
+                q.early_load, q.api, q.mod_id, q.status, q.channel 
             FROM (
                 SELECT mv.name, mv.id, mv.description, mv.version, mv.download_link, 
-                    mv.hash, mv.geode, mv.download_count, mvs.status,
+                    mv.hash, mv.geode, mv.download_count, mvs.status, mv.channel,
                     mv.early_load, mv.api, mv.mod_id, 
-                    row_number() over (partition by m.id order by mv.id desc) rn 
+                    row_number() over (partition by m.id order by mv.version_key desc nulls last, mv.id desc) rn 
                 FROM mods m 
                 INNER JOIN mod_versions mv ON m.id = mv.mod_id
                 INNER JOIN mod_gd_versions mgv ON mgv.mod_id = mv.id
                 INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id
                 WHERE mvs.status = 'accepted'"#,
         );
+        query_builder.push(" AND mv.channel <= ");
+        query_builder.push_bind(channel);
         if let Some(m) = major {
             let major_ver = format!("{}.%", m);
             query_builder.push(" AND mv.version LIKE ");
@@ -439,9 +615,7 @@ impl ModVersion {
         if let Some(g) = gd {
             query_builder.push(" AND (mgv.gd = ");
             query_builder.push_bind(g);
-            query_builder.push(" OR mgv.gd = ");
-            query_builder.push_bind(GDVersionEnum::All);
-            query_builder.push(")");
+            query_builder.push(" OR mgv.gd = '*')");
         }
         for (i, platform) in platforms.iter().enumerate() {
             if i == 0 {
@@ -475,7 +649,7 @@ impl ModVersion {
         let ids: Vec<i32> = vec![version.id];
         version.gd = ModGDVersion::get_for_mod_version(version.id, pool).await?;
         version.dependencies = Some(
-            Dependency::get_for_mod_versions(&ids, None, None, None, pool)
+            Dependency::get_for_mod_versions(&ids, None, None, None, channel, pool)
                 .await?
                 .get(&version.id)
                 .cloned()
@@ -510,13 +684,23 @@ impl ModVersion {
             return Err(ApiError::DbError);
         };
 
+        let channel = match Version::parse(&json.version) {
+            Ok(v) => ModVersionChannel::resolve(&v, json.channel)?,
+            Err(_) => {
+                return Err(ApiError::BadRequest(format!(
+                    "Invalid semver {}",
+                    json.version
+                )))
+            }
+        };
+
         // If someone finds a way to use macros with optional parameters you can impl it here
         let mut builder: QueryBuilder<Postgres> = QueryBuilder::new("INSERT INTO mod_versions (");
         if json.description.is_some() {
             builder.push("description, ");
         }
         builder
-            .push("name, version, download_link, hash, geode, early_load, api, mod_id, status_id) VALUES (");
+            .push("name, version, download_link, hash, geode, early_load, api, mod_id, channel, status_id) VALUES (");
         let mut separated = builder.separated(", ");
         if json.description.is_some() {
             separated.push_bind(&json.description);
@@ -529,6 +713,7 @@ impl ModVersion {
         separated.push_bind(json.early_load);
         separated.push_bind(json.api.is_some());
         separated.push_bind(&json.id);
+        separated.push_bind(channel);
         // set status_id = 0, will be checked by foreign key at the end of the transaction
         separated.push_bind(0);
         separated.push_unseparated(") RETURNING id");
@@ -541,10 +726,69 @@ impl ModVersion {
             Ok(row) => row,
         };
         let id = result.get::<i32, &str>("id");
+        ModVersion::create_related_from_json(id, json, pool).await?;
+
+        let status = if dev_verified {
+            ModVersionStatusEnum::Accepted
+        } else {
+            ModVersionStatusEnum::Pending
+        };
+
+        let status_id =
+            ModVersionStatus::create_for_mod_version(id, status, None, None, pool).await?;
+        ModVersionStatus::record_event(
+            id,
+            ModVersionStatusEvent::Created,
+            Some(status),
+            None,
+            None,
+            pool,
+        )
+        .await?;
+        if let Err(e) = sqlx::query!(
+            "update mod_versions set status_id = $1 where id = $2",
+            status_id,
+            id
+        )
+        .execute(&mut *pool)
+        .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+
+        if let Err(e) = sqlx::query!("SET CONSTRAINTS mod_versions_status_id_fkey IMMEDIATE")
+            .execute(&mut *pool)
+            .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        };
+
+        Ok(())
+    }
+
+    /**
+     * Inserts everything that hangs off a mod version and comes from its mod.json:
+     * tags, gd versions, artifacts, dependencies and incompatibilities
+     */
+    async fn create_related_from_json(
+        id: i32,
+        json: &ModJson,
+        pool: &mut PgConnection,
+    ) -> Result<(), ApiError> {
         let json_tags = json.tags.clone().unwrap_or_default();
-        let tags = Tag::get_tag_ids(json_tags, pool).await?;
-        Tag::update_mod_tags(&json.id, tags.into_iter().map(|x| x.id).collect(), pool).await?;
+        let tags: Vec<i32> = Tag::get_tag_ids(json_tags, pool)
+            .await?
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        Tag::set_mod_version_tags(id, &tags, pool).await?;
+        Tag::update_mod_tags(&json.id, tags, pool).await?;
         ModGDVersion::create_from_json(json.gd.to_create_payload(json), id, pool).await?;
+        if !json.artifacts.is_empty() {
+            ModVersionArtifact::create_for_mod_version(id, &json.artifacts, pool).await?;
+        }
         if json.dependencies.as_ref().is_some_and(|x| !x.is_empty()) {
             let dependencies = json.prepare_dependencies_for_create()?;
             if !dependencies.is_empty() {
@@ -562,18 +806,43 @@ impl ModVersion {
             }
         }
 
-        let status = if dev_verified {
-            ModVersionStatusEnum::Accepted
-        } else {
-            ModVersionStatusEnum::Pending
+        Ok(())
+    }
+
+    /**
+     * Swaps the package of a pending version for a new one with the same version number,
+     * keeping its id and status. Should be called inside a transaction.
+     */
+    pub async fn replace_from_json(
+        id: i32,
+        json: &ModJson,
+        developer_id: i32,
+        pool: &mut PgConnection,
+    ) -> Result<(), ApiError> {
+        let channel = match Version::parse(&json.version) {
+            Ok(v) => ModVersionChannel::resolve(&v, json.channel)?,
+            Err(_) => {
+                return Err(ApiError::BadRequest(format!(
+                    "Invalid semver {}",
+                    json.version
+                )))
+            }
         };
 
-        let status_id =
-            ModVersionStatus::create_for_mod_version(id, status, None, None, pool).await?;
         if let Err(e) = sqlx::query!(
-            "update mod_versions set status_id = $1 where id = $2",
-            status_id,
-            id
+            "UPDATE mod_versions
+            SET name = $2, description = $3, download_link = $4, hash = $5, geode = $6,
+                early_load = $7, api = $8, channel = $9, tags_known = true
+            WHERE id = $1",
+            id,
+            &json.name,
+            json.description,
+            &json.download_url,
+            &json.hash,
+            &json.geode,
+            json.early_load,
+            json.api.is_some(),
+            channel as ModVersionChannel
         )
         .execute(&mut *pool)
         .await
@@ -582,14 +851,82 @@ impl ModVersion {
             return Err(ApiError::DbError);
         }
 
-        if let Err(e) = sqlx::query!("SET CONSTRAINTS mod_versions_status_id_fkey IMMEDIATE")
+        for query in [
+            sqlx::query!("DELETE FROM mod_version_tags WHERE mod_version_id = $1", id),
+            sqlx::query!("DELETE FROM mod_gd_versions WHERE mod_id = $1", id),
+            sqlx::query!(
+                "DELETE FROM mod_version_artifacts WHERE mod_version_id = $1",
+                id
+            ),
+            sqlx::query!("DELETE FROM dependencies WHERE dependent_id = $1", id),
+            sqlx::query!("DELETE FROM incompatibilities WHERE mod_id = $1", id),
+        ] {
+            if let Err(e) = query.execute(&mut *pool).await {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        }
+
+        ModVersion::create_related_from_json(id, json, pool).await?;
+
+        ModVersionStatus::record_event(
+            id,
+            ModVersionStatusEvent::Replaced,
+            Some(ModVersionStatusEnum::Pending),
+            None,
+            Some(developer_id),
+            pool,
+        )
+        .await
+    }
+
+    /**
+     * Deletes a pending version, so its version number can be uploaded again.
+     * The status history is kept.
+     */
+    pub async fn withdraw(
+        id: i32,
+        developer_id: i32,
+        pool: &mut PgConnection,
+    ) -> Result<(), ApiError> {
+        let others = match sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM mod_versions mv
+            WHERE mv.mod_id = (SELECT mod_id FROM mod_versions WHERE id = $1)
+                AND mv.id <> $1"#,
+            id
+        )
+        .fetch_one(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+        if others == 0 {
+            return Err(ApiError::BadRequest(
+                "Can't withdraw the only version of a mod, replace it instead".to_string(),
+            ));
+        }
+
+        ModVersionStatus::record_event(
+            id,
+            ModVersionStatusEvent::Withdrawn,
+            None,
+            None,
+            Some(developer_id),
+            pool,
+        )
+        .await?;
+
+        if let Err(e) = sqlx::query!("DELETE FROM mod_versions WHERE id = $1", id)
             .execute(&mut *pool)
             .await
         {
             log::error!("{}", e);
             return Err(ApiError::DbError);
-        };
-
+        }
         Ok(())
     }
 
@@ -605,7 +942,7 @@ impl ModVersion {
             r#"SELECT mv.id, mv.name, mv.description, mv.version, 
                 mv.download_link, mv.download_count,
                 mv.hash, mv.geode, mv.early_load, mv.api, 
-                mv.mod_id, mvs.status as "status: _" 
+                mv.mod_id, mvs.status as "status: _", mv.channel as "channel: _" 
             FROM mod_versions mv
             INNER JOIN mods m ON m.id = mv.mod_id
             INNER JOIN mod_version_statuses mvs ON mvs.mod_version_id = mv.id 
@@ -631,14 +968,21 @@ impl ModVersion {
             version.gd = ModGDVersion::get_for_mod_version(version.id, pool).await?;
             let ids = vec![version.id];
             version.dependencies = Some(
-                Dependency::get_for_mod_versions(&ids, None, None, None, pool)
-                    .await?
-                    .get(&version.id)
-                    .cloned()
-                    .unwrap_or_default()
-                    .into_iter()
-                    .map(|x| x.to_response())
-                    .collect(),
+                Dependency::get_for_mod_versions(
+                    &ids,
+                    None,
+                    None,
+                    None,
+                    ModVersionChannel::Stable,
+                    pool,
+                )
+                .await?
+                .get(&version.id)
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|x| x.to_response())
+                .collect(),
             );
             let incompat = Incompatibility::get_for_mod_version(version.id, pool).await?;
             version.incompatibilities =
@@ -650,22 +994,28 @@ impl ModVersion {
         Ok(version)
     }
 
-    pub async fn calculate_cached_downloads(
-        mod_version_id: i32,
+    /**
+     * Adds new downloads onto the cached counts of accepted versions, one id per download.
+     * Downloads of other versions are left for the download-cache job, once they're accepted.
+     */
+    pub async fn add_cached_downloads(
+        mod_version_ids: &[i32],
         pool: &mut PgConnection,
     ) -> Result<(), ApiError> {
         if let Err(e) = sqlx::query!(
-            "UPDATE mod_versions mv 
-            SET download_count = mv.download_count + (
-                SELECT COUNT(DISTINCT md.ip) FROM mod_downloads md
-                WHERE md.mod_version_id = mv.id AND md.time_downloaded > mv.last_download_cache_refresh 
-            ), last_download_cache_refresh = now()
-            FROM mod_version_statuses mvs
-            WHERE mv.id = $1 AND mvs.mod_version_id = mv.id AND mvs.status = 'accepted'",
-            mod_version_id
+            "UPDATE mod_versions mv
+            SET download_count = mv.download_count + c.downloads,
+            last_download_cache_refresh = GREATEST(mv.last_download_cache_refresh, now())
+            FROM (
+                SELECT v.id, COUNT(*) AS downloads FROM unnest($1::int[]) AS v(id)
+                GROUP BY v.id
+            ) c, mod_version_statuses mvs
+            WHERE mv.id = c.id AND mvs.mod_version_id = mv.id AND mvs.status = 'accepted'",
+            mod_version_ids
         )
         .execute(&mut *pool)
-        .await {
+        .await
+        {
             log::error!("{}", e);
             return Err(ApiError::DbError);
         }
@@ -681,11 +1031,13 @@ impl ModVersion {
     ) -> Result<(), ApiError> {
         struct CurrentStatusRes {
             status: ModVersionStatusEnum,
+            mod_id: String,
         }
         let current_status = match sqlx::query_as!(
             CurrentStatusRes,
-            r#"select status as "status: _" from mod_version_statuses
-            where mod_version_id = $1"#,
+            r#"select mvs.status as "status: _", mv.mod_id from mod_version_statuses mvs
+            inner join mod_versions mv on mv.id = mvs.mod_version_id
+            where mvs.mod_version_id = $1"#,
             id
         )
         .fetch_one(&mut *pool)
@@ -717,9 +1069,9 @@ impl ModVersion {
         query_builder.push_bind(new_status);
         query_builder.push(", admin_id = ");
         query_builder.push_bind(admin_id);
-        if let Some(i) = info {
+        if let Some(i) = &info {
             query_builder.push(", info = ");
-            query_builder.push_bind(i);
+            query_builder.push_bind(i.clone());
         }
 
         query_builder.push(" WHERE mod_version_id = ");
@@ -730,6 +1082,16 @@ impl ModVersion {
             return Err(ApiError::DbError);
         }
 
+        ModVersionStatus::record_event(
+            id,
+            ModVersionStatusEvent::StatusChanged,
+            Some(new_status),
+            info,
+            Some(admin_id),
+            pool,
+        )
+        .await?;
+
         if current_status.status == ModVersionStatusEnum::Pending
             && new_status == ModVersionStatusEnum::Accepted
         {
@@ -773,6 +1135,9 @@ impl ModVersion {
             };
         }
 
+        // Search only indexes the latest accepted version, which may have just changed
+        Mod::update_search_vector(&current_status.mod_id, pool).await?;
+
         Ok(())
     }
 }
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use sqlx::PgConnection;
+
+use crate::types::api::ApiError;
+
+use super::mod_gd_version::VerPlatform;
+
+#[derive(Debug, Clone)]
+pub struct ModVersionArtifact {
+    pub mod_version_id: i32,
+    pub platform: VerPlatform,
+    pub download_link: String,
+    pub hash: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ModVersionArtifactUpload {
+    pub platform: VerPlatform,
+    pub download_link: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModVersionArtifactCreate {
+    pub platform: VerPlatform,
+    pub download_link: String,
+    pub hash: String,
+}
+
+impl ModVersionArtifact {
+    pub async fn create_for_mod_version(
+        mod_version_id: i32,
+        artifacts: &[ModVersionArtifactCreate],
+        pool: &mut PgConnection,
+    ) -> Result<(), ApiError> {
+        for i in artifacts {
+            if let Err(e) = sqlx::query!(
+                "INSERT INTO mod_version_artifacts (mod_version_id, platform, download_link, hash)
+                VALUES ($1, $2, $3, $4)",
+                mod_version_id,
+                i.platform as VerPlatform,
+                i.download_link,
+                i.hash
+            )
+            .execute(&mut *pool)
+            .await
+            {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn get_for_mod_version(
+        mod_version_id: i32,
+        platform: VerPlatform,
+        pool: &mut PgConnection,
+    ) -> Result<Option<ModVersionArtifact>, ApiError> {
+        match sqlx::query_as!(
+            ModVersionArtifact,
+            r#"SELECT mod_version_id, platform as "platform: _", download_link, hash
+            FROM mod_version_artifacts
+            WHERE mod_version_id = $1 AND platform = $2"#,
+            mod_version_id,
+            platform as VerPlatform
+        )
+        .fetch_optional(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+            Ok(r) => Ok(r),
+        }
+    }
+
+    pub async fn get_for_mod_versions(
+        ids: &Vec<i32>,
+        platform: VerPlatform,
+        pool: &mut PgConnection,
+    ) -> Result<HashMap<i32, ModVersionArtifact>, ApiError> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let records = match sqlx::query_as!(
+            ModVersionArtifact,
+            r#"SELECT mod_version_id, platform as "platform: _", download_link, hash
+            FROM mod_version_artifacts
+            WHERE mod_version_id = ANY($1) AND platform = $2"#,
+            ids,
+            platform as VerPlatform
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+
+        Ok(records.into_iter().map(|x| (x.mod_version_id, x)).collect())
+    }
+}
@@ -0,0 +1,235 @@
+use std::{collections::HashMap, hash::Hash};
+
+use regex::Regex;
+use semver::Version;
+use serde::Serialize;
+use sqlx::PgConnection;
+
+use crate::types::api::ApiError;
+
+use super::{
+    dependency::{Dependency, FetchedDependency, ResponseDependency},
+    incompatibility::{Incompatibility, ResponseIncompatibility},
+    mod_gd_version::{ModGDVersion, VerPlatform},
+    mod_version::ModVersion,
+    tag::Tag,
+};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ValueChange<T> {
+    pub from: T,
+    pub to: T,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ListDiff<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+    pub changed: Vec<ValueChange<T>>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PlatformGDVersion {
+    pub platform: VerPlatform,
+    pub gd: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ChangelogSection {
+    pub version: String,
+    pub content: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ModVersionDiff {
+    pub from: String,
+    pub to: String,
+    pub geode: Option<ValueChange<String>>,
+    pub early_load: Option<ValueChange<bool>>,
+    pub api: Option<ValueChange<bool>>,
+    pub dependencies: ListDiff<ResponseDependency>,
+    pub incompatibilities: ListDiff<ResponseIncompatibility>,
+    pub gd: ListDiff<PlatformGDVersion>,
+    /// None when the tags of either version aren't known
+    pub tags: Option<ListDiff<String>>,
+    pub changelog: Vec<ChangelogSection>,
+}
+
+impl ModVersionDiff {
+    pub async fn get(
+        id: &str,
+        from: &str,
+        to: &str,
+        only_accepted: bool,
+        pool: &mut PgConnection,
+    ) -> Result<ModVersionDiff, ApiError> {
+        let old = ModVersion::get_one(id, from.trim_start_matches('v'), false, only_accepted, pool)
+            .await?;
+        let new =
+            ModVersion::get_one(id, to.trim_start_matches('v'), false, only_accepted, pool).await?;
+        let ids = vec![old.id, new.id];
+
+        let mut deps = Dependency::get_declared_for_mod_versions(&ids, pool).await?;
+        let to_response = |x: Option<Vec<FetchedDependency>>| -> Vec<ResponseDependency> {
+            x.unwrap_or_default()
+                .iter()
+                .map(|d| d.to_response())
+                .collect()
+        };
+        let dependencies = diff_by_key(
+            to_response(deps.remove(&old.id)),
+            to_response(deps.remove(&new.id)),
+            |x| x.mod_id.clone(),
+        );
+
+        let incompatibilities = diff_by_key(
+            Incompatibility::get_for_mod_version(old.id, pool)
+                .await?
+                .iter()
+                .map(|x| x.to_response())
+                .collect(),
+            Incompatibility::get_for_mod_version(new.id, pool)
+                .await?
+                .iter()
+                .map(|x| x.to_response())
+                .collect(),
+            |x| x.mod_id.clone(),
+        );
+
+        let mut gd_versions = ModGDVersion::get_for_mod_versions(&ids, pool).await?;
+        let mut platforms = |id: i32| -> Vec<PlatformGDVersion> {
+            gd_versions
+                .remove(&id)
+                .map(|x| x.platforms())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(platform, gd)| PlatformGDVersion { platform, gd })
+                .collect()
+        };
+        let gd = diff_by_key(platforms(old.id), platforms(new.id), |x| x.platform);
+
+        // Versions uploaded before tags were stored per version don't have any
+        let tags_known = match sqlx::query_scalar!(
+            r#"SELECT bool_and(tags_known) as "known!" FROM mod_versions WHERE id = ANY($1)"#,
+            &ids
+        )
+        .fetch_one(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r,
+        };
+        let tags = if tags_known {
+            let mut tags = Tag::get_tags_for_mod_versions(&ids, pool).await?;
+            Some(diff_by_key(
+                tags.remove(&old.id).unwrap_or_default(),
+                tags.remove(&new.id).unwrap_or_default(),
+                |x| x.clone(),
+            ))
+        } else {
+            None
+        };
+
+        let changelog = match sqlx::query_scalar!("SELECT changelog FROM mods WHERE id = $1", id)
+            .fetch_optional(&mut *pool)
+            .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+            Ok(r) => r.flatten(),
+        };
+        let changelog = match (
+            Version::parse(&old.version),
+            Version::parse(&new.version),
+            changelog,
+        ) {
+            (Ok(a), Ok(b), Some(c)) => changelog_sections_between(&c, &a, &b),
+            _ => vec![],
+        };
+
+        Ok(ModVersionDiff {
+            geode: value_change(&old.geode, &new.geode),
+            early_load: value_change(&old.early_load, &new.early_load),
+            api: value_change(&old.api, &new.api),
+            from: old.version,
+            to: new.version,
+            dependencies,
+            incompatibilities,
+            gd,
+            tags,
+            changelog,
+        })
+    }
+}
+
+fn value_change<T: PartialEq + Clone>(from: &T, to: &T) -> Option<ValueChange<T>> {
+    if from == to {
+        None
+    } else {
+        Some(ValueChange {
+            from: from.clone(),
+            to: to.clone(),
+        })
+    }
+}
+
+fn diff_by_key<T, K, F>(old: Vec<T>, new: Vec<T>, key: F) -> ListDiff<T>
+where
+    T: PartialEq,
+    K: Eq + Hash,
+    F: Fn(&T) -> K,
+{
+    let mut old: HashMap<K, T> = old.into_iter().map(|x| (key(&x), x)).collect();
+    let mut ret = ListDiff {
+        added: vec![],
+        removed: vec![],
+        changed: vec![],
+    };
+
+    for i in new {
+        match old.remove(&key(&i)) {
+            None => ret.added.push(i),
+            Some(o) if o != i => ret.changed.push(ValueChange { from: o, to: i }),
+            Some(_) => {}
+        }
+    }
+    ret.removed = old.into_values().collect();
+
+    ret
+}
+
+/**
+ * Splits a changelog on headings that start with a version (like "## v1.2.0") and returns
+ * the sections for versions after the lower one, up to and including the higher one
+ */
+fn changelog_sections_between(changelog: &str, a: &Version, b: &Version) -> Vec<ChangelogSection> {
+    let (lower, upper) = if a <= b { (a, b) } else { (b, a) };
+    let heading = Regex::new(r"^#+\s*\[?v?(\d+\.\d+\.\d+(?:-[0-9A-Za-z.\-]+)?)").unwrap();
+
+    let mut sections: Vec<(Version, String, Vec<&str>)> = vec![];
+    for line in changelog.lines() {
+        if let Some(version) = heading
+            .captures(line.trim())
+            .and_then(|c| Version::parse(&c[1]).ok())
+        {
+            let name = version.to_string();
+            sections.push((version, name, vec![line]));
+        } else if let Some(last) = sections.last_mut() {
+            last.2.push(line);
+        }
+    }
+
+    sections
+        .into_iter()
+        .filter(|(version, _, _)| version > lower && version <= upper)
+        .map(|(_, version, lines)| ChangelogSection {
+            version,
+            content: lines.join("\n").trim().to_string(),
+        })
+        .collect()
+}
@@ -1,5 +1,9 @@
+use chrono::SecondsFormat;
 use serde::{Deserialize, Serialize};
-use sqlx::PgConnection;
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    PgConnection,
+};
 
 use crate::types::api::ApiError;
 
@@ -13,6 +17,64 @@ pub enum ModVersionStatusEnum {
     Unlisted,
 }
 
+#[derive(sqlx::Type, Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case", type_name = "mod_version_status_event")]
+pub enum ModVersionStatusEvent {
+    Created,
+    StatusChanged,
+    Replaced,
+    Withdrawn,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ModVersionStatusHistoryDeveloper {
+    pub id: i32,
+    pub username: String,
+    pub display_name: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ModVersionStatusHistoryEntry {
+    pub event: ModVersionStatusEvent,
+    pub status: Option<ModVersionStatusEnum>,
+    pub info: Option<String>,
+    pub developer: Option<ModVersionStatusHistoryDeveloper>,
+    pub created_at: String,
+}
+
+struct ModVersionStatusHistoryRecord {
+    event: ModVersionStatusEvent,
+    status: Option<ModVersionStatusEnum>,
+    info: Option<String>,
+    developer_id: Option<i32>,
+    username: Option<String>,
+    display_name: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl ModVersionStatusHistoryRecord {
+    fn into_entry(self) -> ModVersionStatusHistoryEntry {
+        let developer = match (self.developer_id, self.username, self.display_name) {
+            (Some(id), Some(username), Some(display_name)) => {
+                Some(ModVersionStatusHistoryDeveloper {
+                    id,
+                    username,
+                    display_name,
+                })
+            }
+            _ => None,
+        };
+        ModVersionStatusHistoryEntry {
+            event: self.event,
+            status: self.status,
+            info: self.info,
+            developer,
+            created_at: self.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
 pub struct ModVersionStatus {
     pub id: i32,
     pub mod_version_id: i32,
@@ -46,4 +108,65 @@ impl ModVersionStatus {
             Ok(r) => Ok(r.id),
         }
     }
+
+    /**
+     * Appends an entry to the status history of a mod version. The mod id and version are
+     * looked up from the mod version, so this has to be called before withdrawing it.
+     */
+    pub async fn record_event(
+        mod_version_id: i32,
+        event: ModVersionStatusEvent,
+        status: Option<ModVersionStatusEnum>,
+        info: Option<String>,
+        developer_id: Option<i32>,
+        pool: &mut PgConnection,
+    ) -> Result<(), ApiError> {
+        if let Err(e) = sqlx::query!(
+            "INSERT INTO mod_version_status_history
+                (mod_id, version, mod_version_id, event, status, info, developer_id)
+            SELECT mv.mod_id, mv.version, mv.id, $2, $3, $4, $5
+            FROM mod_versions mv
+            WHERE mv.id = $1",
+            mod_version_id,
+            event as ModVersionStatusEvent,
+            status as Option<ModVersionStatusEnum>,
+            info,
+            developer_id
+        )
+        .execute(&mut *pool)
+        .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+        Ok(())
+    }
+
+    pub async fn get_history(
+        mod_id: &str,
+        version: &str,
+        pool: &mut PgConnection,
+    ) -> Result<Vec<ModVersionStatusHistoryEntry>, ApiError> {
+        match sqlx::query_as!(
+            ModVersionStatusHistoryRecord,
+            r#"SELECT h.event as "event: _", h.status as "status: _", h.info,
+                h.developer_id, d.username as "username?", d.display_name as "display_name?",
+                h.created_at
+            FROM mod_version_status_history h
+            LEFT JOIN developers d ON d.id = h.developer_id
+            WHERE h.mod_id = $1 AND h.version = $2
+            ORDER BY h.created_at, h.id"#,
+            mod_id,
+            version
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+            Ok(r) => Ok(r.into_iter().map(|x| x.into_entry()).collect()),
+        }
+    }
 }
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use serde::Serialize;
 use sqlx::{PgConnection, Postgres, QueryBuilder};
 
 use crate::types::api::ApiError;
@@ -10,11 +11,22 @@ pub struct FetchedTag {
     pub name: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct TagDetails {
+    #[serde(skip_serializing)]
+    pub id: i32,
+    pub name: String,
+    pub display_name: String,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub retired: bool,
+}
+
 pub struct Tag;
 
 impl Tag {
     pub async fn get_tags(pool: &mut PgConnection) -> Result<Vec<String>, ApiError> {
-        let tags = match sqlx::query!("SELECT name FROM mod_tags")
+        let tags = match sqlx::query!("SELECT name FROM mod_tags WHERE retired_at IS NULL")
             .fetch_all(&mut *pool)
             .await
         {
@@ -28,13 +40,231 @@ impl Tag {
         Ok(tags.into_iter().map(|x| x.name).collect::<Vec<String>>())
     }
 
+    pub async fn get_detailed_tags(pool: &mut PgConnection) -> Result<Vec<TagDetails>, ApiError> {
+        match sqlx::query_as!(
+            TagDetails,
+            r#"SELECT id, name, display_name, description, icon, retired_at IS NOT NULL as "retired!"
+            FROM mod_tags
+            ORDER BY name"#
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Ok(tags) => Ok(tags),
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+        }
+    }
+
+    pub async fn get_by_name(
+        name: &str,
+        pool: &mut PgConnection,
+    ) -> Result<Option<TagDetails>, ApiError> {
+        match sqlx::query_as!(
+            TagDetails,
+            r#"SELECT id, name, display_name, description, icon, retired_at IS NOT NULL as "retired!"
+            FROM mod_tags
+            WHERE name = $1"#,
+            name.to_lowercase()
+        )
+        .fetch_optional(&mut *pool)
+        .await
+        {
+            Ok(tag) => Ok(tag),
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+        }
+    }
+
+    pub async fn create(
+        name: &str,
+        display_name: Option<&str>,
+        description: Option<&str>,
+        icon: Option<&str>,
+        pool: &mut PgConnection,
+    ) -> Result<TagDetails, ApiError> {
+        let name = Tag::validate_name(name)?;
+        if Tag::get_by_name(&name, pool).await?.is_some() {
+            return Err(ApiError::BadRequest(format!(
+                "Tag '{}' already exists",
+                name
+            )));
+        }
+        let display_name = display_name.unwrap_or(&name).to_string();
+        Tag::validate_details(&display_name, description, icon)?;
+
+        match sqlx::query_as!(
+            TagDetails,
+            r#"INSERT INTO mod_tags (name, display_name, description, icon)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, name, display_name, description, icon, retired_at IS NOT NULL as "retired!""#,
+            name,
+            display_name,
+            description,
+            icon
+        )
+        .fetch_one(&mut *pool)
+        .await
+        {
+            Ok(tag) => Ok(tag),
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+        }
+    }
+
+    /**
+     * Renaming keeps every mod on the tag, since mods reference tags by id
+     */
+    pub async fn update(
+        tag: &TagDetails,
+        name: Option<&str>,
+        display_name: Option<&str>,
+        description: Option<&str>,
+        icon: Option<&str>,
+        retired: Option<bool>,
+        pool: &mut PgConnection,
+    ) -> Result<TagDetails, ApiError> {
+        let name = match name {
+            Some(n) => {
+                let n = Tag::validate_name(n)?;
+                if n != tag.name && Tag::get_by_name(&n, pool).await?.is_some() {
+                    return Err(ApiError::BadRequest(format!("Tag '{}' already exists", n)));
+                }
+                Some(n)
+            }
+            None => None,
+        };
+        Tag::validate_details(display_name.unwrap_or(&tag.display_name), description, icon)?;
+
+        match sqlx::query_as!(
+            TagDetails,
+            r#"UPDATE mod_tags SET
+                name = COALESCE($2, name),
+                display_name = COALESCE($3, display_name),
+                description = COALESCE($4, description),
+                icon = COALESCE($5, icon),
+                retired_at = CASE
+                    WHEN $6::bool IS NULL THEN retired_at
+                    WHEN $6 THEN COALESCE(retired_at, NOW())
+                    ELSE NULL
+                END
+            WHERE id = $1
+            RETURNING id, name, display_name, description, icon, retired_at IS NOT NULL as "retired!""#,
+            tag.id,
+            name,
+            display_name,
+            description,
+            icon,
+            retired
+        )
+        .fetch_one(&mut *pool)
+        .await
+        {
+            Ok(tag) => Ok(tag),
+            Err(e) => {
+                log::error!("{}", e);
+                Err(ApiError::DbError)
+            }
+        }
+    }
+
+    /**
+     * Moves every mod and mod version from one tag to another, then deletes the old tag
+     */
+    pub async fn merge(from: i32, into: i32, pool: &mut PgConnection) -> Result<(), ApiError> {
+        if let Err(e) = sqlx::query!(
+            "INSERT INTO mods_mod_tags (mod_id, tag_id)
+            SELECT mod_id, $2 FROM mods_mod_tags WHERE tag_id = $1
+            ON CONFLICT DO NOTHING",
+            from,
+            into
+        )
+        .execute(&mut *pool)
+        .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+
+        if let Err(e) = sqlx::query!(
+            "INSERT INTO mod_version_tags (mod_version_id, tag_id)
+            SELECT mod_version_id, $2 FROM mod_version_tags WHERE tag_id = $1
+            ON CONFLICT DO NOTHING",
+            from,
+            into
+        )
+        .execute(&mut *pool)
+        .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+
+        if let Err(e) = sqlx::query!("DELETE FROM mod_tags WHERE id = $1", from)
+            .execute(&mut *pool)
+            .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+        Ok(())
+    }
+
+    fn validate_name(name: &str) -> Result<String, ApiError> {
+        let name = name.trim().to_lowercase();
+        if name.is_empty()
+            || name.len() > 32
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err(ApiError::BadRequest(
+                "Tag names have to be 1 to 32 characters long and only contain letters, numbers and dashes".to_string(),
+            ));
+        }
+        Ok(name)
+    }
+
+    fn validate_details(
+        display_name: &str,
+        description: Option<&str>,
+        icon: Option<&str>,
+    ) -> Result<(), ApiError> {
+        if display_name.trim().is_empty() || display_name.len() > 64 {
+            return Err(ApiError::BadRequest(
+                "Tag display names have to be 1 to 64 characters long".to_string(),
+            ));
+        }
+        if description.is_some_and(|d| d.len() > 512) {
+            return Err(ApiError::BadRequest(
+                "Tag descriptions can't be longer than 512 characters".to_string(),
+            ));
+        }
+        if icon.is_some_and(|i| i.len() > 256) {
+            return Err(ApiError::BadRequest(
+                "Tag icons can't be longer than 256 characters".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /**
+     * Unknown and retired tags are skipped, see get_upload_warnings
+     */
     pub async fn get_tag_ids(
         tags: Vec<String>,
         pool: &mut PgConnection,
     ) -> Result<Vec<FetchedTag>, ApiError> {
-        let db_tags = match sqlx::query_as!(FetchedTag, "SELECT id, name FROM mod_tags")
-            .fetch_all(&mut *pool)
-            .await
+        let db_tags = match sqlx::query_as!(
+            FetchedTag,
+            "SELECT id, name FROM mod_tags WHERE retired_at IS NULL"
+        )
+        .fetch_all(&mut *pool)
+        .await
         {
             Ok(tags) => tags,
             Err(e) => {
@@ -43,26 +273,53 @@ impl Tag {
             }
         };
 
-        let mut ret = Vec::new();
+        let mut ret: Vec<FetchedTag> = Vec::new();
         for tag in tags {
             if let Some(t) = db_tags.iter().find(|t| t.name == tag.to_lowercase()) {
-                ret.push(t.clone())
-            } else {
-                return Err(ApiError::BadRequest(format!(
-                    "Tag '{}' isn't allowed. Only the following are allowed: '{}'",
-                    tag,
-                    db_tags
-                        .iter()
-                        .map(|t| t.name.clone())
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                )));
+                if !ret.iter().any(|x| x.id == t.id) {
+                    ret.push(t.clone())
+                }
             }
         }
 
         Ok(ret)
     }
 
+    /**
+     * Warnings for the tags of an upload that will be left off the mod
+     */
+    pub async fn get_upload_warnings(
+        tags: &[String],
+        pool: &mut PgConnection,
+    ) -> Result<Vec<String>, ApiError> {
+        if tags.is_empty() {
+            return Ok(vec![]);
+        }
+        let db_tags =
+            match sqlx::query!("SELECT name, retired_at IS NOT NULL as retired FROM mod_tags")
+                .fetch_all(&mut *pool)
+                .await
+            {
+                Ok(tags) => tags,
+                Err(e) => {
+                    log::error!("{}", e);
+                    return Err(ApiError::DbError);
+                }
+            };
+
+        let mut ret = vec![];
+        for tag in tags {
+            match db_tags.iter().find(|t| t.name == tag.to_lowercase()) {
+                None => ret.push(format!("Unknown tag '{}' was ignored", tag)),
+                Some(t) if t.retired.unwrap_or(false) => {
+                    ret.push(format!("Tag '{}' is retired and was ignored", tag))
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(ret)
+    }
+
     pub async fn update_mod_tags(
         mod_id: &str,
         tags: Vec<i32>,
@@ -208,6 +465,70 @@ impl Tag {
         Ok(ret)
     }
 
+    pub async fn set_mod_version_tags(
+        mod_version_id: i32,
+        tags: &[i32],
+        pool: &mut PgConnection,
+    ) -> Result<(), ApiError> {
+        if let Err(e) = sqlx::query!(
+            "DELETE FROM mod_version_tags WHERE mod_version_id = $1",
+            mod_version_id
+        )
+        .execute(&mut *pool)
+        .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+
+        if tags.is_empty() {
+            return Ok(());
+        }
+
+        if let Err(e) = sqlx::query!(
+            "INSERT INTO mod_version_tags (mod_version_id, tag_id)
+            SELECT $1, UNNEST($2::int4[])",
+            mod_version_id,
+            tags
+        )
+        .execute(&mut *pool)
+        .await
+        {
+            log::error!("{}", e);
+            return Err(ApiError::DbError);
+        }
+        Ok(())
+    }
+
+    pub async fn get_tags_for_mod_versions(
+        ids: &Vec<i32>,
+        pool: &mut PgConnection,
+    ) -> Result<HashMap<i32, Vec<String>>, ApiError> {
+        let tags = match sqlx::query!(
+            "SELECT mod_tags.name, mod_version_tags.mod_version_id FROM mod_tags
+            INNER JOIN mod_version_tags ON mod_tags.id = mod_version_tags.tag_id
+            WHERE mod_version_tags.mod_version_id = ANY($1)
+            ORDER BY mod_tags.name",
+            ids
+        )
+        .fetch_all(&mut *pool)
+        .await
+        {
+            Ok(tags) => tags,
+            Err(e) => {
+                log::error!("{}", e);
+                return Err(ApiError::DbError);
+            }
+        };
+
+        let mut ret: HashMap<i32, Vec<String>> = HashMap::new();
+        for tag in tags {
+            ret.entry(tag.mod_version_id).or_default().push(tag.name);
+        }
+
+        Ok(ret)
+    }
+
     pub async fn parse_tags(tags: &str, pool: &mut PgConnection) -> Result<Vec<i32>, ApiError> {
         let tags = tags
             .split(',')